@@ -0,0 +1,260 @@
+; Snake, after Nick Morgan's easy6502 tutorial.
+;
+; The screen is 32x32 pixels at $0200-$05FF, one byte per pixel holding a
+; color index. The host writes the last key pressed to $FF and a random
+; byte to $FE before every instruction.
+
+.define appleL      $00     ; screen location of the apple
+.define appleH      $01
+.define snakeDirection $02  ; one of the movingX bits below
+.define snakeLength $03     ; in bytes, two per segment
+.define snakeHeadL  $10     ; screen locations, head first
+.define snakeHeadH  $11
+.define snakeBodyStart $12
+
+.define sysRandom   $fe
+.define sysLastKey  $ff
+
+.define ASCII_w     'w'
+.define ASCII_a     'a'
+.define ASCII_s     's'
+.define ASCII_d     'd'
+
+.define movingUp    1
+.define movingRight 2
+.define movingDown  4
+.define movingLeft  8
+
+    .org $0600
+
+    jsr init
+    jsr loop
+
+init:
+    jsr initSnake
+    jsr generateApplePosition
+    rts
+
+initSnake:
+    lda #movingRight
+    sta snakeDirection
+
+    lda #4                  ; two segments
+    sta snakeLength
+
+    lda #$11
+    sta snakeHeadL
+    lda #$10
+    sta snakeBodyStart
+    lda #$0f
+    sta snakeBodyStart + 2
+
+    lda #$04
+    sta snakeHeadH
+    sta snakeBodyStart + 1
+    sta snakeBodyStart + 3
+    rts
+
+generateApplePosition:
+    ; low byte is anything
+    lda sysRandom
+    sta appleL
+
+    ; high byte is 2-5, one of the four screen pages
+    lda sysRandom
+    and #$03
+    clc
+    adc #2
+    sta appleH
+    rts
+
+loop:
+    jsr readKeys
+    jsr checkCollision
+    jsr updateSnake
+    jsr drawApple
+    jsr drawSnake
+    jsr spinWheels
+    jmp loop
+
+readKeys:
+    lda sysLastKey
+    cmp #ASCII_w
+    beq upKey
+    cmp #ASCII_d
+    beq rightKey
+    cmp #ASCII_s
+    beq downKey
+    cmp #ASCII_a
+    beq leftKey
+    rts
+
+; a key opposite to the current direction is ignored
+upKey:
+    lda #movingDown
+    bit snakeDirection
+    bne illegalMove
+    lda #movingUp
+    sta snakeDirection
+    rts
+
+rightKey:
+    lda #movingLeft
+    bit snakeDirection
+    bne illegalMove
+    lda #movingRight
+    sta snakeDirection
+    rts
+
+downKey:
+    lda #movingUp
+    bit snakeDirection
+    bne illegalMove
+    lda #movingDown
+    sta snakeDirection
+    rts
+
+leftKey:
+    lda #movingRight
+    bit snakeDirection
+    bne illegalMove
+    lda #movingLeft
+    sta snakeDirection
+    rts
+
+illegalMove:
+    rts
+
+checkCollision:
+    jsr checkAppleCollision
+    jsr checkSnakeCollision
+    rts
+
+checkAppleCollision:
+    lda appleL
+    cmp snakeHeadL
+    bne @done
+    lda appleH
+    cmp snakeHeadH
+    bne @done
+
+    ; eat the apple and grow a segment
+    inc snakeLength
+    inc snakeLength
+    jsr generateApplePosition
+@done:
+    rts
+
+checkSnakeCollision:
+    ldx #2                  ; start with the second segment
+@loop:
+    lda snakeHeadL,x
+    cmp snakeHeadL
+    bne @continue
+    lda snakeHeadH,x
+    cmp snakeHeadH
+    beq @collided
+@continue:
+    inx
+    inx
+    cpx snakeLength
+    beq @done
+    jmp @loop
+@collided:
+    jmp gameOver
+@done:
+    rts
+
+updateSnake:
+    ldx snakeLength
+    dex
+    txa
+@shift:                     ; move every segment one place down the body
+    lda snakeHeadL,x
+    sta snakeBodyStart,x
+    dex
+    bpl @shift
+
+    ; the direction bits, lowest first, say which way the head moves
+    lda snakeDirection
+    lsr a
+    bcs up
+    lsr a
+    bcs right
+    lsr a
+    bcs down
+    lsr a
+    bcs left
+
+up:
+    lda snakeHeadL
+    sec
+    sbc #$20                ; one row
+    sta snakeHeadL
+    bcc @upup
+    rts
+@upup:
+    dec snakeHeadH
+    lda #$01
+    cmp snakeHeadH
+    beq collision
+    rts
+
+right:
+    inc snakeHeadL
+    lda #$1f
+    bit snakeHeadL
+    beq collision
+    rts
+
+down:
+    lda snakeHeadL
+    clc
+    adc #$20
+    sta snakeHeadL
+    bcs @downdown
+    rts
+@downdown:
+    inc snakeHeadH
+    lda #$06
+    cmp snakeHeadH
+    beq collision
+    rts
+
+left:
+    dec snakeHeadL
+    lda snakeHeadL
+    and #$1f
+    cmp #$1f
+    beq collision
+    rts
+
+collision:
+    jmp gameOver
+
+drawApple:
+    ldy #0
+    lda sysRandom
+    sta (appleL),y
+    rts
+
+drawSnake:
+    ldx snakeLength
+    lda #0
+    sta (snakeHeadL,x)      ; erase the end of the tail
+
+    ldx #0
+    lda #1
+    sta (snakeHeadL,x)      ; paint the head
+    rts
+
+spinWheels:
+    ldx sysLastKey
+@spin:
+    nop
+    nop
+    dex
+    bne @spin
+    rts
+
+gameOver:
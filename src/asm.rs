@@ -0,0 +1,842 @@
+use crate::constants::{AddressingMode, CMOS_OP_CODES, CPU_OP_CODES, OpCode};
+use crate::cpu::CpuVariant;
+use std::collections::HashMap;
+use std::fmt;
+
+// Where code goes until the first .org, and where CPU::load puts programs.
+pub const DEFAULT_ORIGIN: u16 = 0x0600;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    Syntax(String),
+    UnknownInstruction(String),
+    UnknownDirective(String),
+    UndefinedSymbol(String),
+    DuplicateSymbol(String),
+    // the instruction exists, just not with this kind of operand
+    InvalidAddressing(String),
+    BranchOutOfRange(i64),
+    ValueOutOfRange(i64),
+    // a .org moved back over bytes already emitted at this address
+    Overlap(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::Syntax(message) => write!(f, "{message}"),
+            AsmErrorKind::UnknownInstruction(name) => write!(f, "Unknown instruction {name}"),
+            AsmErrorKind::UnknownDirective(name) => write!(f, "Unknown directive {name}"),
+            AsmErrorKind::UndefinedSymbol(name) => write!(f, "Undefined symbol {name}"),
+            AsmErrorKind::DuplicateSymbol(name) => write!(f, "Symbol {name} is already defined"),
+            AsmErrorKind::InvalidAddressing(name) => {
+                write!(f, "Addressing mode not available for {name}")
+            }
+            AsmErrorKind::BranchOutOfRange(offset) => {
+                write!(
+                    f,
+                    "Branch target is {offset} bytes away, outside -128..=127"
+                )
+            }
+            AsmErrorKind::ValueOutOfRange(value) => write!(f, "Value {value:#x} does not fit"),
+            AsmErrorKind::Overlap(addr) => write!(f, "Code at ${addr:04X} overlaps earlier output"),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+// An assembled image. Gaps between .org blocks are zero filled.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    // labels and constants; locals appear as "scope@name"
+    pub symbols: HashMap<String, i64>,
+}
+
+// Assembles 2A03 code into bytes for CPU::load, so it should start at
+// DEFAULT_ORIGIN; use assemble_program() for anything placed elsewhere.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_program(CpuVariant::Ricoh2A03, source).map(|program| program.bytes)
+}
+
+pub fn assemble_program(variant: CpuVariant, source: &str) -> Result<Program, AsmError> {
+    let mut lines = parse(source)?;
+    let symbols = first_pass(variant, &mut lines)?;
+    second_pass(&lines, symbols)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum UnaryOp {
+    Neg,
+    Not,
+    Lo,
+    Hi,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Symbol(String),
+    // the address of the current line, written *
+    Pc,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    Direct(Expr),
+    IndexedX(Expr),
+    IndexedY(Expr),
+    Indirect(Expr),
+    IndirectX(Expr),
+    IndirectY(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Data {
+    Value(Expr),
+    Text(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Empty,
+    Org(Expr),
+    Define(String, Expr),
+    Byte(Vec<Data>),
+    Word(Vec<Expr>),
+    Instruction { mnemonic: String, operand: Operand },
+}
+
+struct Line {
+    number: usize,
+    label: Option<String>,
+    statement: Statement,
+    // picked in the first pass so both passes agree on the size
+    opcode: Option<&'static OpCode>,
+}
+
+fn syntax(message: impl Into<String>) -> AsmErrorKind {
+    AsmErrorKind::Syntax(message.into())
+}
+
+fn parse(source: &str) -> Result<Vec<Line>, AsmError> {
+    let mut scope = String::new();
+    let mut lines = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let number = i + 1;
+        let line = parse_line(strip_comment(text), &mut scope)
+            .map_err(|kind| AsmError { line: number, kind })?;
+        let (label, statement) = line;
+        lines.push(Line {
+            number,
+            label,
+            statement,
+            opcode: None,
+        });
+    }
+    Ok(lines)
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ';') => return &text[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    text
+}
+
+fn is_symbol_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '@'
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Splits a leading symbol off `text`, if there is one.
+fn take_symbol(text: &str) -> Option<(&str, &str)> {
+    if !text.starts_with(is_symbol_start) {
+        return None;
+    }
+    let end = text[1..]
+        .find(|c| !is_symbol_char(c))
+        .map_or(text.len(), |i| i + 1);
+    Some((&text[..end], &text[end..]))
+}
+
+// @name is local to the last global label before it.
+fn qualify(name: &str, scope: &str) -> String {
+    if name.starts_with('@') {
+        format!("{scope}{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+fn parse_line(text: &str, scope: &mut String) -> Result<(Option<String>, Statement), AsmErrorKind> {
+    let mut rest = text.trim();
+    let mut label = None;
+    if let Some((name, after)) = take_symbol(rest)
+        && let Some(after) = after.strip_prefix(':')
+    {
+        if !name.starts_with('@') {
+            *scope = name.to_string();
+        }
+        label = Some(qualify(name, scope));
+        rest = after.trim();
+    }
+
+    if rest.is_empty() {
+        return Ok((label, Statement::Empty));
+    }
+
+    if let Some(directive) = rest.strip_prefix('.') {
+        let (name, args) = directive
+            .split_once(char::is_whitespace)
+            .unwrap_or((directive, ""));
+        let statement = match name.to_ascii_lowercase().as_str() {
+            "org" => Statement::Org(parse_expr(args, scope)?),
+            "byte" => Statement::Byte(
+                split_list(args)?
+                    .into_iter()
+                    .map(|item| parse_data(item, scope))
+                    .collect::<Result<_, _>>()?,
+            ),
+            "word" => Statement::Word(
+                split_list(args)?
+                    .into_iter()
+                    .map(|item| parse_expr(item, scope))
+                    .collect::<Result<_, _>>()?,
+            ),
+            "define" => {
+                let args = args.trim();
+                let (name, value) =
+                    take_symbol(args).ok_or_else(|| syntax(".define needs a name"))?;
+                let value = value.trim();
+                let value = value.strip_prefix('=').unwrap_or(value);
+                Statement::Define(qualify(name, scope), parse_expr(value, scope)?)
+            }
+            _ => return Err(AsmErrorKind::UnknownDirective(format!(".{name}"))),
+        };
+        return Ok((label, statement));
+    }
+
+    // NAME = expr
+    if let Some((name, after)) = take_symbol(rest)
+        && let Some(value) = after.trim_start().strip_prefix('=')
+    {
+        return Ok((
+            label,
+            Statement::Define(qualify(name, scope), parse_expr(value, scope)?),
+        ));
+    }
+
+    let (mnemonic, operand) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let statement = Statement::Instruction {
+        mnemonic: mnemonic.to_ascii_uppercase(),
+        operand: parse_operand(operand, scope)?,
+    };
+    Ok((label, statement))
+}
+
+// Splits on commas that are not inside quotes.
+fn split_list(text: &str) -> Result<Vec<&str>, AsmErrorKind> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ',') => {
+                items.push(text[start..i].trim());
+                start = i + 1;
+            }
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    items.push(text[start..].trim());
+    if items.iter().any(|item| item.is_empty()) {
+        return Err(syntax("empty item in list"));
+    }
+    Ok(items)
+}
+
+fn parse_data(item: &str, scope: &str) -> Result<Data, AsmErrorKind> {
+    match item.strip_prefix('"') {
+        Some(text) => {
+            let text = text
+                .strip_suffix('"')
+                .ok_or_else(|| syntax("unterminated string"))?;
+            Ok(Data::Text(text.bytes().collect()))
+        }
+        None => Ok(Data::Value(parse_expr(item, scope)?)),
+    }
+}
+
+// Index of the parenthesis closing the one at the start of `text`.
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// (expr) where the outer parentheses wrap everything, as opposed to (1+2)*3
+fn parenthesized(text: &str) -> Option<&str> {
+    if text.starts_with('(') && closing_paren(text) == Some(text.len() - 1) {
+        Some(&text[1..text.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn strip_index<'a>(text: &'a str, register: &str) -> Option<&'a str> {
+    let split = text.len().checked_sub(2)?;
+    if text.is_char_boundary(split) && text[split..].eq_ignore_ascii_case(register) {
+        Some(&text[..split])
+    } else {
+        None
+    }
+}
+
+fn parse_operand(text: &str, scope: &str) -> Result<Operand, AsmErrorKind> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let text = text.as_str();
+    let expr = |text: &str| parse_expr(text, scope);
+
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+    if text.eq_ignore_ascii_case("A") {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand::Immediate(expr(value)?));
+    }
+    if let Some(inner) = parenthesized(text) {
+        return Ok(match strip_index(inner, ",X") {
+            Some(base) => Operand::IndirectX(expr(base)?),
+            None => Operand::Indirect(expr(inner)?),
+        });
+    }
+    if let Some(base) = strip_index(text, ",X") {
+        return Ok(Operand::IndexedX(expr(base)?));
+    }
+    if let Some(base) = strip_index(text, ",Y") {
+        return Ok(match parenthesized(base) {
+            Some(inner) => Operand::IndirectY(expr(inner)?),
+            None => Operand::IndexedY(expr(base)?),
+        });
+    }
+    Ok(Operand::Direct(expr(text)?))
+}
+
+fn parse_expr(text: &str, scope: &str) -> Result<Expr, AsmErrorKind> {
+    let mut parser = ExprParser {
+        chars: text.chars().collect(),
+        pos: 0,
+        scope,
+    };
+    let expr = parser.binary(0)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(syntax(format!(
+            "unexpected '{}' in expression",
+            parser.chars[parser.pos]
+        )));
+    }
+    Ok(expr)
+}
+
+// lowest precedence first
+const BINARY_OPS: [&[(&str, BinaryOp)]; 6] = [
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Mod),
+    ],
+];
+
+struct ExprParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    scope: &'a str,
+}
+
+impl ExprParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matches = token
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += token.chars().count();
+        }
+        matches
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, AsmErrorKind> {
+        if level == BINARY_OPS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for &(token, op) in BINARY_OPS[level] {
+                if self.eat(token) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, AsmErrorKind> {
+        let op = match self.peek() {
+            Some('-') => UnaryOp::Neg,
+            Some('~') => UnaryOp::Not,
+            Some('<') => UnaryOp::Lo,
+            Some('>') => UnaryOp::Hi,
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<Expr, AsmErrorKind> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_digit(radix)) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        i64::from_str_radix(&digits, radix)
+            .map(Expr::Number)
+            .map_err(|_| syntax("malformed number"))
+    }
+
+    fn primary(&mut self) -> Result<Expr, AsmErrorKind> {
+        let c = self.peek().ok_or_else(|| syntax("missing operand"))?;
+        match c {
+            '(' => {
+                self.pos += 1;
+                let expr = self.binary(0)?;
+                if !self.eat(")") {
+                    return Err(syntax("missing ')'"));
+                }
+                Ok(expr)
+            }
+            '$' => {
+                self.pos += 1;
+                self.digits(16)
+            }
+            '%' => {
+                self.pos += 1;
+                self.digits(2)
+            }
+            '0'..='9' => self.digits(10),
+            '*' => {
+                self.pos += 1;
+                Ok(Expr::Pc)
+            }
+            '\'' => match self.chars.get(self.pos + 1..self.pos + 3) {
+                Some(&[c, '\'']) if c.is_ascii() => {
+                    self.pos += 3;
+                    Ok(Expr::Number(c as i64))
+                }
+                _ => Err(syntax("malformed character literal")),
+            },
+            c if is_symbol_start(c) => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.chars.get(self.pos).is_some_and(|&c| is_symbol_char(c)) {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                Ok(Expr::Symbol(qualify(&name, self.scope)))
+            }
+            c => Err(syntax(format!("unexpected '{c}' in expression"))),
+        }
+    }
+}
+
+fn eval(expr: &Expr, symbols: &HashMap<String, i64>, pc: u16) -> Result<i64, AsmErrorKind> {
+    Ok(match expr {
+        Expr::Number(n) => *n,
+        Expr::Symbol(name) => *symbols
+            .get(name)
+            .ok_or_else(|| AsmErrorKind::UndefinedSymbol(name.clone()))?,
+        Expr::Pc => pc as i64,
+        Expr::Unary(op, inner) => {
+            let value = eval(inner, symbols, pc)?;
+            match op {
+                UnaryOp::Neg => -value,
+                UnaryOp::Not => !value,
+                UnaryOp::Lo => value & 0xff,
+                UnaryOp::Hi => (value >> 8) & 0xff,
+            }
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, symbols, pc)?;
+            let rhs = eval(rhs, symbols, pc)?;
+            match op {
+                BinaryOp::Or => lhs | rhs,
+                BinaryOp::Xor => lhs ^ rhs,
+                BinaryOp::And => lhs & rhs,
+                BinaryOp::Shl => lhs.checked_shl(rhs as u32).unwrap_or(0),
+                BinaryOp::Shr => lhs.checked_shr(rhs as u32).unwrap_or(0),
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                BinaryOp::Div | BinaryOp::Mod if rhs == 0 => {
+                    return Err(syntax("division by zero"));
+                }
+                BinaryOp::Div => lhs / rhs,
+                BinaryOp::Mod => lhs % rhs,
+            }
+        }
+    })
+}
+
+// The opcodes the variant actually decodes for a mnemonic; unofficial NMOS
+// opcodes are left out, so NOP is always $EA and SBC #imm is always $E9.
+fn opcodes(variant: CpuVariant, mnemonic: &str) -> Vec<&'static OpCode> {
+    let cmos: &'static [OpCode] = match variant {
+        CpuVariant::Cmos65C02 => &CMOS_OP_CODES,
+        CpuVariant::Ricoh2A03 | CpuVariant::Nmos6502 => &[],
+    };
+    let mut found: Vec<&'static OpCode> = Vec::new();
+    for op in CPU_OP_CODES.iter().chain(cmos) {
+        if op.name == mnemonic
+            && std::ptr::eq(variant.opcode(op.code), op)
+            && !found.iter().any(|f| f.add_mode == op.add_mode)
+        {
+            found.push(op);
+        }
+    }
+    found
+}
+
+// Chooses the opcode for an operand. A value already known to fit in a byte
+// gets the zero page form; forward references get the absolute one.
+fn select_opcode(
+    variant: CpuVariant,
+    mnemonic: &str,
+    operand: &Operand,
+    symbols: &HashMap<String, i64>,
+    pc: u16,
+) -> Result<&'static OpCode, AsmErrorKind> {
+    let candidates = opcodes(variant, mnemonic);
+    if candidates.is_empty() {
+        return Err(AsmErrorKind::UnknownInstruction(mnemonic.to_string()));
+    }
+    let find = |mode| candidates.iter().copied().find(|op| op.add_mode == mode);
+    let sized = |expr: &Expr, zero_page, absolute| {
+        let fits = matches!(eval(expr, symbols, pc), Ok(0..=0xff));
+        match (find(zero_page), find(absolute)) {
+            (Some(op), _) if fits => Some(op),
+            (_, Some(op)) => Some(op),
+            (zp, None) => zp,
+        }
+    };
+
+    let op = match operand {
+        Operand::None => find(AddressingMode::NoneAddressing).or(find(AddressingMode::Accumulator)),
+        Operand::Accumulator => find(AddressingMode::Accumulator),
+        Operand::Immediate(_) => find(AddressingMode::Immediate),
+        Operand::Direct(expr) => find(AddressingMode::Relative)
+            .or_else(|| sized(expr, AddressingMode::ZeroPage, AddressingMode::Absolute)),
+        Operand::IndexedX(expr) => {
+            sized(expr, AddressingMode::ZeroPage_X, AddressingMode::Absolute_X)
+        }
+        Operand::IndexedY(expr) => {
+            sized(expr, AddressingMode::ZeroPage_Y, AddressingMode::Absolute_Y)
+        }
+        Operand::Indirect(_) => {
+            find(AddressingMode::Indirect).or(find(AddressingMode::ZeroPage_Indirect))
+        }
+        Operand::IndirectX(_) => {
+            find(AddressingMode::Indirect_X).or(find(AddressingMode::Absolute_X_Indirect))
+        }
+        Operand::IndirectY(_) => find(AddressingMode::Indirect_Y),
+    };
+    op.ok_or_else(|| AsmErrorKind::InvalidAddressing(mnemonic.to_string()))
+}
+
+fn define(symbols: &mut HashMap<String, i64>, name: &str, value: i64) -> Result<(), AsmErrorKind> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(AsmErrorKind::DuplicateSymbol(name.to_string()));
+    }
+    Ok(())
+}
+
+fn address(pc: i64) -> Result<u16, AsmErrorKind> {
+    u16::try_from(pc).map_err(|_| AsmErrorKind::ValueOutOfRange(pc))
+}
+
+// Lays out every line, picks each instruction's opcode and collects the
+// symbol table.
+fn first_pass(variant: CpuVariant, lines: &mut [Line]) -> Result<HashMap<String, i64>, AsmError> {
+    let mut symbols = HashMap::new();
+    // constants whose value needs a label defined further down
+    let mut pending = Vec::new();
+    let mut pc = DEFAULT_ORIGIN as i64;
+
+    for line in lines.iter_mut() {
+        let number = line.number;
+        let at = |kind| AsmError { line: number, kind };
+        let here = address(pc).map_err(at)?;
+        if let Some(label) = &line.label {
+            define(&mut symbols, label, pc).map_err(at)?;
+        }
+        match &line.statement {
+            Statement::Empty => {}
+            Statement::Org(expr) => pc = eval(expr, &symbols, here).map_err(at)?,
+            Statement::Define(name, expr) => match eval(expr, &symbols, here) {
+                Ok(value) => define(&mut symbols, name, value).map_err(at)?,
+                Err(AsmErrorKind::UndefinedSymbol(_)) => {
+                    pending.push((number, name.clone(), expr.clone(), here))
+                }
+                Err(kind) => return Err(at(kind)),
+            },
+            Statement::Byte(items) => {
+                for item in items {
+                    pc += match item {
+                        Data::Value(_) => 1,
+                        Data::Text(text) => text.len() as i64,
+                    };
+                }
+            }
+            Statement::Word(items) => pc += 2 * items.len() as i64,
+            Statement::Instruction { mnemonic, operand } => {
+                let op = select_opcode(variant, mnemonic, operand, &symbols, here).map_err(at)?;
+                pc += op.bytes as i64;
+                line.opcode = Some(op);
+            }
+        }
+        // the last byte may sit at $FFFF, but nothing may run past it
+        if pc > 0x10000 {
+            return Err(at(AsmErrorKind::ValueOutOfRange(pc)));
+        }
+    }
+
+    // keep going while each round defines something new
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut first_error = None;
+        let mut unresolved = Vec::new();
+        for (number, name, expr, here) in pending {
+            match eval(&expr, &symbols, here) {
+                Ok(value) => define(&mut symbols, &name, value)
+                    .map_err(|kind| AsmError { line: number, kind })?,
+                Err(kind) => {
+                    first_error.get_or_insert(AsmError { line: number, kind });
+                    unresolved.push((number, name, expr, here));
+                }
+            }
+        }
+        if unresolved.len() == before
+            && let Some(error) = first_error
+        {
+            return Err(error);
+        }
+        pending = unresolved;
+    }
+    Ok(symbols)
+}
+
+fn check_range(value: i64, min: i64, max: i64) -> Result<i64, AsmErrorKind> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(AsmErrorKind::ValueOutOfRange(value))
+    }
+}
+
+// Negative values are stored as two's complement, so LDA #-1 is LDA #$FF.
+fn byte(value: i64) -> Result<u8, AsmErrorKind> {
+    check_range(value, -0x80, 0xff).map(|value| value as u8)
+}
+
+fn word(value: i64) -> Result<[u8; 2], AsmErrorKind> {
+    check_range(value, -0x8000, 0xffff).map(|value| (value as u16).to_le_bytes())
+}
+
+fn operand_expr(operand: &Operand) -> Option<&Expr> {
+    match operand {
+        Operand::None | Operand::Accumulator => None,
+        Operand::Immediate(expr)
+        | Operand::Direct(expr)
+        | Operand::IndexedX(expr)
+        | Operand::IndexedY(expr)
+        | Operand::Indirect(expr)
+        | Operand::IndirectX(expr)
+        | Operand::IndirectY(expr) => Some(expr),
+    }
+}
+
+fn encode(
+    op: &OpCode,
+    operand: &Operand,
+    symbols: &HashMap<String, i64>,
+    pc: u16,
+) -> Result<Vec<u8>, AsmErrorKind> {
+    let mut bytes = vec![op.code];
+    let Some(expr) = operand_expr(operand) else {
+        return Ok(bytes);
+    };
+    let value = eval(expr, symbols, pc)?;
+    match (op.add_mode, op.bytes) {
+        (AddressingMode::Relative, _) => {
+            let offset = value - (pc as i64 + 2);
+            if !(-128..=127).contains(&offset) {
+                return Err(AsmErrorKind::BranchOutOfRange(offset));
+            }
+            bytes.push(offset as u8);
+        }
+        (AddressingMode::Immediate, _) => bytes.push(byte(value)?),
+        (_, 2) => bytes.push(check_range(value, 0, 0xff)? as u8),
+        _ => bytes.extend(check_range(value, 0, 0xffff).and_then(word)?),
+    }
+    Ok(bytes)
+}
+
+// Evaluates every operand now that all symbols are known, and lays the
+// output into one image.
+fn second_pass(lines: &[Line], symbols: HashMap<String, i64>) -> Result<Program, AsmError> {
+    let mut chunks: Vec<(u16, Vec<u8>)> = Vec::new();
+    let mut pc = DEFAULT_ORIGIN;
+
+    for line in lines {
+        let at = |kind| AsmError {
+            line: line.number,
+            kind,
+        };
+        let bytes = match &line.statement {
+            Statement::Empty | Statement::Define(..) => continue,
+            Statement::Org(expr) => {
+                pc = eval(expr, &symbols, pc).and_then(address).map_err(at)?;
+                continue;
+            }
+            Statement::Byte(items) => {
+                let mut bytes = Vec::new();
+                for item in items {
+                    match item {
+                        Data::Value(expr) => {
+                            bytes.push(eval(expr, &symbols, pc).and_then(byte).map_err(at)?)
+                        }
+                        Data::Text(text) => bytes.extend(text),
+                    }
+                }
+                bytes
+            }
+            Statement::Word(items) => {
+                let mut bytes = Vec::new();
+                for expr in items {
+                    bytes.extend(eval(expr, &symbols, pc).and_then(word).map_err(at)?);
+                }
+                bytes
+            }
+            Statement::Instruction { operand, .. } => {
+                let op = line.opcode.expect("opcode chosen in the first pass");
+                encode(op, operand, &symbols, pc).map_err(at)?
+            }
+        };
+        let end = pc as usize + bytes.len();
+        let overlap = chunks.iter().find(|(start, chunk)| {
+            (*start as usize) < end && (pc as usize) < *start as usize + chunk.len()
+        });
+        if let Some((start, _)) = overlap {
+            return Err(at(AsmErrorKind::Overlap(pc.max(*start))));
+        }
+        match chunks.last_mut() {
+            Some((start, chunk)) if *start as usize + chunk.len() == pc as usize => {
+                chunk.extend(&bytes)
+            }
+            _ => chunks.push((pc, bytes.clone())),
+        }
+        pc = pc.wrapping_add(bytes.len() as u16);
+    }
+
+    let origin = chunks
+        .iter()
+        .map(|(start, _)| *start)
+        .min()
+        .unwrap_or(DEFAULT_ORIGIN);
+    let end = chunks
+        .iter()
+        .map(|(start, chunk)| *start as usize + chunk.len())
+        .max()
+        .unwrap_or(origin as usize);
+    let mut image = vec![0; end - origin as usize];
+    for (start, chunk) in chunks {
+        let offset = (start - origin) as usize;
+        image[offset..offset + chunk.len()].copy_from_slice(&chunk);
+    }
+    Ok(Program {
+        origin,
+        bytes: image,
+        symbols,
+    })
+}
@@ -1,16 +1,177 @@
+use crate::cartridge::{Region, Rom, RomError};
+use crate::mapper::{self, Mapper};
+use crate::mem::Mem;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
 const RAM: u16 = 0x0000;
 const RAM_MIRRORS_END: u16 = 0x1FFF;
 const PPU_REGISTERS: u16 = 0x2000;
 const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const APU_IO_REGISTERS: u16 = 0x4000;
+const APU_IO_REGISTERS_END: u16 = 0x401F;
+const CARTRIDGE_SPACE: u16 = 0x4020;
+
+const PPUCTRL: usize = 0;
+const PPUMASK: usize = 1;
+const APU_STATUS: usize = 0x15;
+
+// What the 2 KiB of work RAM holds at power-on. Real consoles come up with a
+// mostly random pattern; some games read it before clearing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RamInit {
+    #[default]
+    Zeros,
+    Ones,
+    // the same seed gives the same pattern on every run
+    Random(u64),
+}
 
 pub struct Bus {
+    pub ram_init: RamInit,
+    // taken from the cartridge header when there is one
+    pub region: Region,
     cpu_vram: [u8; 2048],
+    // latches for $2000-$2007 and $4000-$401F until the PPU and APU exist
+    ppu_registers: [u8; 8],
+    apu_io_registers: [u8; 0x20],
+    // $4020-$FFFF as plain memory when no cartridge is inserted
+    cartridge_space: Vec<u8>,
+    mapper: Option<Box<dyn Mapper>>,
 }
 
 impl Bus {
     pub fn new() -> Self {
-        Bus {
+        Self::with_ram_init(RamInit::default())
+    }
+
+    pub fn with_ram_init(ram_init: RamInit) -> Self {
+        let mut bus = Bus {
+            ram_init,
+            region: Region::default(),
             cpu_vram: [0; 2048],
+            ppu_registers: [0; 8],
+            apu_io_registers: [0; 0x20],
+            cartridge_space: vec![0; 0x10000 - CARTRIDGE_SPACE as usize],
+            mapper: None,
+        };
+        bus.power_on();
+        bus
+    }
+
+    pub fn with_rom(rom: Rom) -> Result<Self, RomError> {
+        let region = rom.timing.region();
+        let mut bus = Self::with_mapper(mapper::for_rom(rom)?);
+        bus.region = region;
+        Ok(bus)
+    }
+
+    // for cartridges that do not come from an iNES file
+    pub fn with_mapper(mapper: Box<dyn Mapper>) -> Self {
+        let mut bus = Self::new();
+        bus.mapper = Some(mapper);
+        bus
+    }
+
+    // pattern tables, mirroring and IRQs, for the PPU and the main loop
+    pub fn mapper(&self) -> Option<&dyn Mapper> {
+        self.mapper.as_deref()
+    }
+
+    pub fn mapper_mut(&mut self) -> Option<&mut (dyn Mapper + 'static)> {
+        self.mapper.as_deref_mut()
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mem for Bus {
+    fn power_on(&mut self) {
+        match self.ram_init {
+            RamInit::Zeros => self.cpu_vram.fill(0x00),
+            RamInit::Ones => self.cpu_vram.fill(0xFF),
+            RamInit::Random(seed) => StdRng::seed_from_u64(seed).fill(&mut self.cpu_vram[..]),
+        }
+        self.ppu_registers.fill(0);
+        self.apu_io_registers.fill(0);
+        if let Some(mapper) = &mut self.mapper {
+            mapper.power_on();
+        }
+    }
+
+    // RAM survives the reset button; the PPU drops PPUCTRL and PPUMASK and
+    // the APU silences its channels
+    fn reset(&mut self) {
+        self.ppu_registers[PPUCTRL] = 0;
+        self.ppu_registers[PPUMASK] = 0;
+        self.apu_io_registers[APU_STATUS] = 0;
+        if let Some(mapper) = &mut self.mapper {
+            mapper.reset();
+        }
+    }
+
+    fn clock(&mut self, cycles: u64) {
+        if let Some(mapper) = &mut self.mapper {
+            for _ in 0..cycles {
+                mapper.cpu_cycle();
+            }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.mapper.as_ref().is_some_and(|mapper| mapper.irq())
+    }
+
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        if addr >= CARTRIDGE_SPACE
+            && let Some(mapper) = &mut self.mapper
+        {
+            return mapper.cpu_read(addr);
+        }
+        self.mem_peek(addr)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        match addr {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0111_1111_1111;
+                self.cpu_vram[mirror_down_addr as usize]
+            }
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0000_0000_0111;
+                self.ppu_registers[mirror_down_addr as usize]
+            }
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(addr - APU_IO_REGISTERS) as usize]
+            }
+            CARTRIDGE_SPACE..=0xFFFF => match &self.mapper {
+                Some(mapper) => mapper.cpu_peek(addr),
+                None => self.cartridge_space[(addr - CARTRIDGE_SPACE) as usize],
+            },
+        }
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0111_1111_1111;
+                self.cpu_vram[mirror_down_addr as usize] = data;
+            }
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b0000_0000_0000_0111;
+                self.ppu_registers[mirror_down_addr as usize] = data;
+            }
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(addr - APU_IO_REGISTERS) as usize] = data;
+            }
+            CARTRIDGE_SPACE..=0xFFFF => match &mut self.mapper {
+                Some(mapper) => mapper.cpu_write(addr, data),
+                None => self.cartridge_space[(addr - CARTRIDGE_SPACE) as usize] = data,
+            },
         }
     }
 }
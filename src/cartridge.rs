@@ -0,0 +1,300 @@
+use std::fmt;
+
+const NES_TAG: [u8; 4] = [b'N', b'E', b'S', 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+pub const PRG_ROM_PAGE_SIZE: usize = 0x4000;
+pub const CHR_ROM_PAGE_SIZE: usize = 0x2000;
+const PRG_RAM_PAGE_SIZE: usize = 0x2000;
+
+// How the PPU's two nametables fill its four nametable slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Vertical,
+    Horizontal,
+    // the cartridge brings 2 KiB of extra VRAM
+    FourScreen,
+    // all four slots show the first or the second nametable, under mapper
+    // control
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+// Which header layout a file uses, going by the nesdev detection rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    // bytes 7-15 hold junk from an old dumper and are ignored
+    Archaic,
+    INes,
+    Nes2,
+}
+
+// CPU/PPU timing the cartridge was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    // works on both; runs as NTSC
+    MultiRegion,
+    Dendy,
+}
+
+impl Timing {
+    pub fn region(self) -> Region {
+        match self {
+            Timing::Ntsc | Timing::MultiRegion => Region::Ntsc,
+            Timing::Pal => Region::Pal,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+}
+
+// The console the emulator pretends to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    // the Famiclone timing: PAL frame rate, NTSC-like CPU clock
+    Dendy,
+}
+
+impl Region {
+    pub fn cpu_clock_hz(self) -> u32 {
+        match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    // PPU and hardware type codes, both 0 in iNES 1.0 headers
+    VsSystem { ppu: u8, hardware: u8 },
+    Playchoice10,
+    // NES 2.0 extended console type from byte 13
+    Extended(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    BadMagic,
+    // the header promises more PRG/CHR data (or a trainer) than the file has
+    Truncated { expected: usize, actual: usize },
+    NoPrgRom,
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::BadMagic => write!(f, "Not an iNES file"),
+            RomError::Truncated { expected, actual } => {
+                write!(
+                    f,
+                    "ROM file is truncated: expected {expected} bytes, got {actual}"
+                )
+            }
+            RomError::NoPrgRom => write!(f, "ROM has no PRG ROM"),
+            RomError::UnsupportedMapper(mapper) => write!(f, "Mapper {mapper} is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+#[derive(Debug, Clone)]
+pub struct Rom {
+    pub format: HeaderFormat,
+    pub prg_rom: Vec<u8>,
+    // CHR RAM (and CHR NVRAM) when the header lists no CHR ROM, 8 KiB unless
+    // a NES 2.0 header says otherwise
+    pub chr_rom: Vec<u8>,
+    pub chr_is_ram: bool,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub screen_mirroring: Mirroring,
+    // battery-backed PRG RAM at $6000-$7FFF, kept by save games
+    pub battery: bool,
+    // 512 bytes the mapper places at $7000-$71FF
+    pub trainer: Option<Vec<u8>>,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub timing: Timing,
+    pub console: ConsoleType,
+    // NES 2.0 byte 15: controller or other device plugged in by default
+    pub expansion_device: u8,
+}
+
+// NES 2.0 ROM sizes: a 12-bit count of units, or, when the high nibble is
+// $F, 2^exponent * (multiplier * 2 + 1) bytes packed into the low byte.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> usize {
+    if msb == 0xF {
+        let multiplier = (lsb & 0b11) as usize * 2 + 1;
+        1usize
+            .checked_shl((lsb >> 2) as u32)
+            .and_then(|size| size.checked_mul(multiplier))
+            .unwrap_or(usize::MAX)
+    } else {
+        ((msb as usize) << 8 | lsb as usize) * unit
+    }
+}
+
+// NES 2.0 RAM sizes are 64 << shift bytes, with 0 meaning none.
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
+
+fn header_format(raw: &[u8]) -> HeaderFormat {
+    let nes2_size = || {
+        HEADER_SIZE
+            .saturating_add(nes2_rom_size(raw[4], raw[9] & 0x0F, PRG_ROM_PAGE_SIZE))
+            .saturating_add(nes2_rom_size(raw[5], raw[9] >> 4, CHR_ROM_PAGE_SIZE))
+    };
+    match raw[7] & 0x0C {
+        0x08 if nes2_size() <= raw.len() => HeaderFormat::Nes2,
+        0x00 if raw[12..16].iter().all(|&b| b == 0) => HeaderFormat::INes,
+        _ => HeaderFormat::Archaic,
+    }
+}
+
+impl Rom {
+    // Parses an iNES or NES 2.0 image, whatever its mapper:
+    //
+    //   0-3   "NES" $1A
+    //   4     PRG ROM size in 16 KiB units
+    //   5     CHR ROM size in 8 KiB units, 0 for CHR RAM
+    //   6     mapper low nibble | four-screen | trainer | battery | vertical
+    //   7     mapper high nibble | NES 2.0 tag (bits 2-3) | console type
+    //
+    // iNES 1.0 adds the PRG RAM size in 8 KiB units at byte 8 and a PAL bit
+    // at byte 9. NES 2.0 uses bytes 8-15 for mapper bits 8-11 and the
+    // submapper, the size high nibbles, RAM sizes, timing, Vs. System type,
+    // miscellaneous ROM count and the default expansion device.
+    pub fn new(raw: &[u8]) -> Result<Rom, RomError> {
+        if raw.len() < HEADER_SIZE || raw[0..4] != NES_TAG {
+            return Err(RomError::BadMagic);
+        }
+        let format = header_format(raw);
+
+        let four_screen = raw[6] & 0b1000 != 0;
+        let vertical_mirroring = raw[6] & 0b1 != 0;
+        let screen_mirroring = match (four_screen, vertical_mirroring) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+        let battery = raw[6] & 0b10 != 0;
+        let has_trainer = raw[6] & 0b100 != 0;
+
+        let mut mapper = (raw[6] >> 4) as u16;
+        let mut submapper = 0;
+        let mut console = ConsoleType::Nes;
+        let mut timing = Timing::Ntsc;
+        let mut expansion_device = 0;
+        let prg_rom_size;
+        let chr_rom_size;
+        let (prg_ram_size, prg_nvram_size, chr_ram_size, chr_nvram_size);
+
+        if format != HeaderFormat::Archaic {
+            mapper |= (raw[7] & 0xF0) as u16;
+            console = match raw[7] & 0b11 {
+                0 => ConsoleType::Nes,
+                1 => ConsoleType::VsSystem {
+                    ppu: 0,
+                    hardware: 0,
+                },
+                2 => ConsoleType::Playchoice10,
+                _ => ConsoleType::Extended(0),
+            };
+        }
+
+        if format == HeaderFormat::Nes2 {
+            mapper |= ((raw[8] & 0x0F) as u16) << 8;
+            submapper = raw[8] >> 4;
+            prg_rom_size = nes2_rom_size(raw[4], raw[9] & 0x0F, PRG_ROM_PAGE_SIZE);
+            chr_rom_size = nes2_rom_size(raw[5], raw[9] >> 4, CHR_ROM_PAGE_SIZE);
+            prg_ram_size = nes2_ram_size(raw[10] & 0x0F);
+            prg_nvram_size = nes2_ram_size(raw[10] >> 4);
+            chr_ram_size = nes2_ram_size(raw[11] & 0x0F);
+            chr_nvram_size = nes2_ram_size(raw[11] >> 4);
+            timing = match raw[12] & 0b11 {
+                0 => Timing::Ntsc,
+                1 => Timing::Pal,
+                2 => Timing::MultiRegion,
+                _ => Timing::Dendy,
+            };
+            console = match console {
+                ConsoleType::VsSystem { .. } => ConsoleType::VsSystem {
+                    ppu: raw[13] & 0x0F,
+                    hardware: raw[13] >> 4,
+                },
+                ConsoleType::Extended(_) => ConsoleType::Extended(raw[13] & 0x0F),
+                other => other,
+            };
+            expansion_device = raw[15] & 0x3F;
+        } else {
+            prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
+            chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+            // 0 still means 8 KiB, for compatibility with dumps that left it blank
+            let ram_pages = if format == HeaderFormat::INes { raw[8].max(1) } else { 1 };
+            let ram_size = ram_pages as usize * PRG_RAM_PAGE_SIZE;
+            (prg_ram_size, prg_nvram_size) = if battery { (0, ram_size) } else { (ram_size, 0) };
+            (chr_ram_size, chr_nvram_size) = if chr_rom_size == 0 {
+                (CHR_ROM_PAGE_SIZE, 0)
+            } else {
+                (0, 0)
+            };
+            if format == HeaderFormat::INes && raw[9] & 1 != 0 {
+                timing = Timing::Pal;
+            }
+        }
+
+        if prg_rom_size == 0 {
+            return Err(RomError::NoPrgRom);
+        }
+
+        let trainer_start = HEADER_SIZE;
+        let prg_rom_start = trainer_start + if has_trainer { TRAINER_SIZE } else { 0 };
+        let chr_rom_start = prg_rom_start.saturating_add(prg_rom_size);
+        let expected = chr_rom_start.saturating_add(chr_rom_size);
+        if raw.len() < expected {
+            return Err(RomError::Truncated {
+                expected,
+                actual: raw.len(),
+            });
+        }
+
+        let chr_is_ram = chr_rom_size == 0;
+        let chr_rom = if chr_is_ram {
+            let size = chr_ram_size + chr_nvram_size;
+            vec![0; if size == 0 { CHR_ROM_PAGE_SIZE } else { size }]
+        } else {
+            raw[chr_rom_start..expected].to_vec()
+        };
+
+        Ok(Rom {
+            format,
+            prg_rom: raw[prg_rom_start..chr_rom_start].to_vec(),
+            chr_rom,
+            chr_is_ram,
+            mapper,
+            submapper,
+            screen_mirroring,
+            battery,
+            trainer: has_trainer.then(|| raw[trainer_start..prg_rom_start].to_vec()),
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size,
+            chr_nvram_size,
+            timing,
+            console,
+            expansion_device,
+        })
+    }
+}
@@ -1,7 +1,12 @@
+use lazy_static::lazy_static;
 use std::fmt;
 
 pub const STACK_OFFSET: u16 = 0x100;
 
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_BRK_VECTOR: u16 = 0xFFFE;
+
 pub enum StatusFlag {
     Carry = 0b0000_0001,
     Zero = 0b0000_0010,
@@ -13,23 +18,73 @@ pub enum StatusFlag {
     Negative = 0b1000_0000,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum InterruptType {
+    NMI,
+    IRQ,
+    BRK,
+}
+
+#[derive(Clone, Copy)]
+pub struct Interrupt {
+    pub itype: InterruptType,
+    pub vector_addr: u16,
+    // bits set in the copy of the status pushed to the stack
+    pub b_flag_mask: u8,
+    pub cpu_cycles: u8,
+}
+
+pub const NMI: Interrupt = Interrupt {
+    itype: InterruptType::NMI,
+    vector_addr: NMI_VECTOR,
+    b_flag_mask: StatusFlag::Unused as u8,
+    cpu_cycles: 7,
+};
+
+pub const IRQ: Interrupt = Interrupt {
+    itype: InterruptType::IRQ,
+    vector_addr: IRQ_BRK_VECTOR,
+    b_flag_mask: StatusFlag::Unused as u8,
+    cpu_cycles: 7,
+};
+
+// the 7 cycles of BRK are already counted by its opcode entry
+pub const BRK: Interrupt = Interrupt {
+    itype: InterruptType::BRK,
+    vector_addr: IRQ_BRK_VECTOR,
+    b_flag_mask: StatusFlag::Break as u8 | StatusFlag::Unused as u8,
+    cpu_cycles: 0,
+};
+
 #[derive(Debug, Clone)]
-pub struct StackError {
-    pub counter: u16,
-    pub err_msg: String,
+pub enum CpuError {
+    // a KIL/JAM opcode locked up the CPU; only a reset recovers it
+    Jam { opcode: u8, counter: u16 },
+    // an instruction needed an operand address its mode does not have; the
+    // decode tables never pair them, so this only guards against table bugs
+    InvalidAddressing { mode: AddressingMode, counter: u16 },
 }
 
-impl fmt::Display for StackError {
+impl fmt::Display for CpuError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let counter = self.counter;
-        let msg = self.err_msg.clone();
-        write!(f, "{msg} on program counter: {counter}")
+        match self {
+            CpuError::Jam { opcode, counter } => {
+                write!(f, "CPU jammed by opcode {opcode:#04x} at {counter:#06x}")
+            }
+            CpuError::InvalidAddressing { mode, counter } => {
+                write!(f, "Addressing mode {mode:?} has no operand at {counter:#06x}")
+            }
+        }
     }
 }
 
-#[derive(Debug)]
+impl std::error::Error for CpuError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
+    Accumulator,
     Immediate,
     ZeroPage,
     ZeroPage_X,
@@ -40,6 +95,9 @@ pub enum AddressingMode {
     Indirect,
     Indirect_X,
     Indirect_Y,
+    // 65C02 only: ($zp) and JMP ($abs,X)
+    ZeroPage_Indirect,
+    Absolute_X_Indirect,
     NoneAddressing,
     Relative
 }
@@ -52,14 +110,14 @@ pub struct OpCode {
     pub add_mode: AddressingMode,
 }
 
-pub static CPU_OP_CODES: [OpCode; 151] = [
+pub static CPU_OP_CODES: [OpCode; 256] = [
     OpCode {code: 0x00, name: "BRK", bytes: 1, cycles: 7, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0xaa, name: "TAX", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0x8a, name: "TXA", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0xa8, name: "TAY", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0xba, name: "TSX", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
-    OpCode {code: 0xaa, name: "TXS", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
-    OpCode {code: 0x8a, name: "TYA", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x9a, name: "TXS", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x98, name: "TYA", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0xe8, name: "INX", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0xc8, name: "INY", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0xca, name: "DEX", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
@@ -76,18 +134,18 @@ pub static CPU_OP_CODES: [OpCode; 151] = [
     OpCode {code: 0x60, name: "RTS", bytes: 1, cycles: 6, add_mode: AddressingMode::NoneAddressing},
 
     OpCode {code: 0x48, name: "PHA", bytes: 1, cycles: 3, add_mode: AddressingMode::NoneAddressing},
-    OpCode {code: 0x68, name: "PLA", bytes: 1, cycles: 3, add_mode: AddressingMode::NoneAddressing},
-    OpCode {code: 0x08, name: "PHP", bytes: 1, cycles: 4, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x68, name: "PLA", bytes: 1, cycles: 4, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x08, name: "PHP", bytes: 1, cycles: 3, add_mode: AddressingMode::NoneAddressing},
     OpCode {code: 0x28, name: "PLP", bytes: 1, cycles: 4, add_mode: AddressingMode::NoneAddressing},
     
-    OpCode {code: 0x90, name: "BCC", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0xb0, name: "BCS", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0xf0, name: "BEQ", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0xd0, name: "BNE", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0x30, name: "BMI", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0x10, name: "BPL", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0x70, name: "BVS", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
-    OpCode {code: 0x50, name: "BVC", bytes: 2, cycles: 2, /* +1 if branch succeeds +2 if to a new Page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0x90, name: "BCC", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0xb0, name: "BCS", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0xf0, name: "BEQ", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0xd0, name: "BNE", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0x30, name: "BMI", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0x10, name: "BPL", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0x70, name: "BVS", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+    OpCode {code: 0x50, name: "BVC", bytes: 2, cycles: 2, /* +1 if branch succeeds, +1 more if to a new page */ add_mode: AddressingMode::Relative},
 
     OpCode {code: 0xa9, name: "LDA", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
     OpCode {code: 0xa5, name: "LDA", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
@@ -171,25 +229,25 @@ pub static CPU_OP_CODES: [OpCode; 151] = [
     OpCode {code: 0x01, name: "ORA", bytes: 2, cycles: 6, add_mode: AddressingMode::Indirect_X},
     OpCode {code: 0x11, name: "ORA", bytes: 2, cycles: 5, /* +1 if page crossed */ add_mode: AddressingMode::Indirect_Y},
     
-    OpCode {code: 0x0a, name: "ASL", bytes: 1, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x0a, name: "ASL", bytes: 1, cycles: 2, add_mode: AddressingMode::Accumulator},
     OpCode {code: 0x06, name: "ASL", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
     OpCode {code: 0x16, name: "ASL", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
     OpCode {code: 0x0e, name: "ASL", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
     OpCode {code: 0x1e, name: "ASL", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
 
-    OpCode {code: 0x4a, name: "LSR", bytes: 1, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x4a, name: "LSR", bytes: 1, cycles: 2, add_mode: AddressingMode::Accumulator},
     OpCode {code: 0x46, name: "LSR", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
     OpCode {code: 0x56, name: "LSR", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
     OpCode {code: 0x4e, name: "LSR", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
     OpCode {code: 0x5e, name: "LSR", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
 
-    OpCode {code: 0x2a, name: "ROL", bytes: 1, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x2a, name: "ROL", bytes: 1, cycles: 2, add_mode: AddressingMode::Accumulator},
     OpCode {code: 0x26, name: "ROL", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
     OpCode {code: 0x36, name: "ROL", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
     OpCode {code: 0x2e, name: "ROL", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
     OpCode {code: 0x3e, name: "ROL", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
 
-    OpCode {code: 0x6a, name: "ROR", bytes: 1, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x6a, name: "ROR", bytes: 1, cycles: 2, add_mode: AddressingMode::Accumulator},
     OpCode {code: 0x66, name: "ROR", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
     OpCode {code: 0x76, name: "ROR", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
     OpCode {code: 0x6e, name: "ROR", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
@@ -228,9 +286,283 @@ pub static CPU_OP_CODES: [OpCode; 151] = [
     OpCode {code: 0x4c, name: "JMP", bytes: 3, cycles: 3, add_mode: AddressingMode::Absolute},
     OpCode {code: 0x6c, name: "JMP", bytes: 3, cycles: 5, add_mode: AddressingMode::Indirect},
 
-    OpCode {code: 0x20, name: "JSR", bytes: 3, cycles: 3, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x20, name: "JSR", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+
+    // unofficial opcodes
+    OpCode {code: 0x1a, name: "*NOP", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x3a, name: "*NOP", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x5a, name: "*NOP", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x7a, name: "*NOP", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xda, name: "*NOP", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xfa, name: "*NOP", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x80, name: "*NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x82, name: "*NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x89, name: "*NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0xc2, name: "*NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0xe2, name: "*NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x04, name: "*NOP", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x44, name: "*NOP", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x64, name: "*NOP", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x14, name: "*NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x34, name: "*NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x54, name: "*NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x74, name: "*NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0xd4, name: "*NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0xf4, name: "*NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x0c, name: "*NOP", bytes: 3, cycles: 4, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x1c, name: "*NOP", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x3c, name: "*NOP", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x5c, name: "*NOP", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x7c, name: "*NOP", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0xdc, name: "*NOP", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0xfc, name: "*NOP", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+
+    OpCode {code: 0xa7, name: "*LAX", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0xb7, name: "*LAX", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_Y},
+    OpCode {code: 0xaf, name: "*LAX", bytes: 3, cycles: 4, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0xbf, name: "*LAX", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0xa3, name: "*LAX", bytes: 2, cycles: 6, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0xb3, name: "*LAX", bytes: 2, cycles: 5, /* +1 if page crossed */ add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x87, name: "*SAX", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x97, name: "*SAX", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_Y},
+    OpCode {code: 0x8f, name: "*SAX", bytes: 3, cycles: 4, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x83, name: "*SAX", bytes: 2, cycles: 6, add_mode: AddressingMode::Indirect_X},
+
+    OpCode {code: 0xeb, name: "*SBC", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0xc7, name: "*DCP", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0xd7, name: "*DCP", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0xcf, name: "*DCP", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0xdf, name: "*DCP", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0xdb, name: "*DCP", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0xc3, name: "*DCP", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0xd3, name: "*DCP", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0xe7, name: "*ISB", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0xf7, name: "*ISB", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0xef, name: "*ISB", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0xff, name: "*ISB", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0xfb, name: "*ISB", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0xe3, name: "*ISB", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0xf3, name: "*ISB", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x07, name: "*SLO", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x17, name: "*SLO", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x0f, name: "*SLO", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x1f, name: "*SLO", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x1b, name: "*SLO", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0x03, name: "*SLO", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0x13, name: "*SLO", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x27, name: "*RLA", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x37, name: "*RLA", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x2f, name: "*RLA", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x3f, name: "*RLA", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x3b, name: "*RLA", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0x23, name: "*RLA", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0x33, name: "*RLA", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x47, name: "*SRE", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x57, name: "*SRE", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x4f, name: "*SRE", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x5f, name: "*SRE", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x5b, name: "*SRE", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0x43, name: "*SRE", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0x53, name: "*SRE", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x67, name: "*RRA", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x77, name: "*RRA", bytes: 2, cycles: 6, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x6f, name: "*RRA", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x7f, name: "*RRA", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_X},
+    OpCode {code: 0x7b, name: "*RRA", bytes: 3, cycles: 7, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0x63, name: "*RRA", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_X},
+    OpCode {code: 0x73, name: "*RRA", bytes: 2, cycles: 8, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x0b, name: "*ANC", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x2b, name: "*ANC", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0x4b, name: "*ALR", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0x6b, name: "*ARR", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0xcb, name: "*AXS", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0xbb, name: "*LAS", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_Y},
+
+    // unstable: behavior depends on the chip, see UnstableOpcodes in cpu.rs
+    OpCode {code: 0x8b, name: "*XAA", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0xab, name: "*LXA", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+
+    OpCode {code: 0x9f, name: "*AHX", bytes: 3, cycles: 5, add_mode: AddressingMode::Absolute_Y},
+    OpCode {code: 0x93, name: "*AHX", bytes: 2, cycles: 6, add_mode: AddressingMode::Indirect_Y},
+
+    OpCode {code: 0x9e, name: "*SHX", bytes: 3, cycles: 5, add_mode: AddressingMode::Absolute_Y},
+
+    OpCode {code: 0x9c, name: "*SHY", bytes: 3, cycles: 5, add_mode: AddressingMode::Absolute_X},
+
+    OpCode {code: 0x9b, name: "*TAS", bytes: 3, cycles: 5, add_mode: AddressingMode::Absolute_Y},
+
+    OpCode {code: 0x02, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x12, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x22, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x32, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x42, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x52, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x62, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x72, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x92, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xb2, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xd2, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xf2, name: "*JAM", bytes: 1, cycles: 2, add_mode: AddressingMode::NoneAddressing},
     ];
-    
-    pub fn find_opcode(code: u8) -> Option<&'static OpCode> {
-        CPU_OP_CODES.iter().find(|op| op.code == code)
-    }
\ No newline at end of file
+
+// 65C02 opcodes that differ from the NMOS table: new instructions, the
+// zero-page indirect mode, and NOPs in place of every unofficial opcode
+pub static CMOS_OP_CODES: [OpCode; 106] = [
+    // JMP ($xxFF) reads its pointer without wrapping and takes one more cycle
+    OpCode {code: 0x6c, name: "JMP", bytes: 3, cycles: 6, add_mode: AddressingMode::Indirect},
+    OpCode {code: 0x7c, name: "JMP", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute_X_Indirect},
+
+    OpCode {code: 0x80, name: "BRA", bytes: 2, cycles: 2, /* +1 always, +1 more if to a new page */ add_mode: AddressingMode::Relative},
+
+    OpCode {code: 0xda, name: "PHX", bytes: 1, cycles: 3, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x5a, name: "PHY", bytes: 1, cycles: 3, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xfa, name: "PLX", bytes: 1, cycles: 4, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x7a, name: "PLY", bytes: 1, cycles: 4, add_mode: AddressingMode::NoneAddressing},
+
+    OpCode {code: 0x64, name: "STZ", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x74, name: "STZ", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x9c, name: "STZ", bytes: 3, cycles: 4, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x9e, name: "STZ", bytes: 3, cycles: 5, add_mode: AddressingMode::Absolute_X},
+
+    OpCode {code: 0x14, name: "TRB", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x1c, name: "TRB", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x04, name: "TSB", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x0c, name: "TSB", bytes: 3, cycles: 6, add_mode: AddressingMode::Absolute},
+
+    OpCode {code: 0x1a, name: "INC", bytes: 1, cycles: 2, add_mode: AddressingMode::Accumulator},
+    OpCode {code: 0x3a, name: "DEC", bytes: 1, cycles: 2, add_mode: AddressingMode::Accumulator},
+
+    OpCode {code: 0x89, name: "BIT", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x34, name: "BIT", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x3c, name: "BIT", bytes: 3, cycles: 4, /* +1 if page crossed */ add_mode: AddressingMode::Absolute_X},
+
+    OpCode {code: 0x12, name: "ORA", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0x32, name: "AND", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0x52, name: "EOR", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0x72, name: "ADC", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0x92, name: "STA", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0xb2, name: "LDA", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0xd2, name: "CMP", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+    OpCode {code: 0xf2, name: "SBC", bytes: 2, cycles: 5, add_mode: AddressingMode::ZeroPage_Indirect},
+
+    // every other unused opcode is a NOP; the bit instructions of later
+    // WDC and Rockwell parts (RMB, SMB, BBR, BBS) are one-byte NOPs here
+    OpCode {code: 0x02, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x22, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x42, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x62, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x82, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0xc2, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0xe2, name: "NOP", bytes: 2, cycles: 2, add_mode: AddressingMode::Immediate},
+    OpCode {code: 0x44, name: "NOP", bytes: 2, cycles: 3, add_mode: AddressingMode::ZeroPage},
+    OpCode {code: 0x54, name: "NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0xd4, name: "NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0xf4, name: "NOP", bytes: 2, cycles: 4, add_mode: AddressingMode::ZeroPage_X},
+    OpCode {code: 0x5c, name: "NOP", bytes: 3, cycles: 8, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0xdc, name: "NOP", bytes: 3, cycles: 4, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0xfc, name: "NOP", bytes: 3, cycles: 4, add_mode: AddressingMode::Absolute},
+    OpCode {code: 0x03, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x13, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x23, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x33, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x43, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x53, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x63, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x73, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x83, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x93, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xa3, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xb3, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xc3, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xd3, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xe3, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xf3, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x0b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x1b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x2b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x3b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x4b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x5b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x6b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x7b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x8b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x9b, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xab, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xbb, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xcb, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xdb, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xeb, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xfb, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x07, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x17, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x27, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x37, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x47, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x57, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x67, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x77, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x87, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x97, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xa7, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xb7, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xc7, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xd7, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xe7, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xf7, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x0f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x1f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x2f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x3f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x4f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x5f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x6f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x7f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x8f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0x9f, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xaf, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xbf, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xcf, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xdf, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xef, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+    OpCode {code: 0xff, name: "NOP", bytes: 1, cycles: 1, add_mode: AddressingMode::NoneAddressing},
+];
+
+lazy_static! {
+    // decode table indexed by opcode byte
+    pub static ref OPCODES_MAP: [&'static OpCode; 256] = {
+        let mut map = [&CPU_OP_CODES[0]; 256];
+        for op in CPU_OP_CODES.iter() {
+            map[op.code as usize] = op;
+        }
+        map
+    };
+
+    pub static ref CMOS_OPCODES_MAP: [&'static OpCode; 256] = {
+        let mut map = *OPCODES_MAP;
+        for op in CMOS_OP_CODES.iter() {
+            map[op.code as usize] = op;
+        }
+        map
+    };
+}
+
+pub fn find_opcode(code: u8) -> &'static OpCode {
+    OPCODES_MAP[code as usize]
+}
+
+pub fn find_cmos_opcode(code: u8) -> &'static OpCode {
+    CMOS_OPCODES_MAP[code as usize]
+}
@@ -1,103 +1,268 @@
-use crate::constants::{AddressingMode, STACK_OFFSET, StackError, StatusFlag, find_opcode};
+use crate::bus::Bus;
+use crate::constants::{
+    AddressingMode, BRK, CpuError, IRQ, Interrupt, InterruptType, NMI, OpCode, RESET_VECTOR,
+    STACK_OFFSET, StatusFlag, find_cmos_opcode, find_opcode,
+};
+use crate::mem::Mem;
+use crate::trace::{self, Diagnostic, TraceSink};
+
+mod cycle;
+
+use cycle::InFlight;
+
+// Model for the unstable unofficial opcodes. Their results depend on analog
+// effects that differ between chips, so the knobs below pick which observed
+// behavior to reproduce.
+//   XAA ($8B): A = (A | xaa_magic) & X & imm
+//   LXA ($AB): A = X = (A | lxa_magic) & imm
+//   AHX/SHX/SHY ($93, $9F, $9E, $9C): store reg & (H + 1), where H is the high
+//     byte of the base address; TAS ($9B) first sets SP = A & X and stores SP & (H + 1)
+//   LAS ($BB): A = X = SP = mem & SP
+// When the indexed address of a SH* store crosses a page, real hardware puts the
+// stored value on the high address lines; sh_page_cross_corrupts_address
+// reproduces that, otherwise the store goes to the computed address.
+#[derive(Debug, Clone, Copy)]
+pub struct UnstableOpcodes {
+    pub xaa_magic: u8,
+    pub lxa_magic: u8,
+    pub sh_page_cross_corrupts_address: bool,
+}
+
+impl Default for UnstableOpcodes {
+    fn default() -> Self {
+        UnstableOpcodes {
+            xaa_magic: 0xEE,
+            lxa_magic: 0xFF,
+            sh_page_cross_corrupts_address: true,
+        }
+    }
+}
+
+// Which chip the core behaves like. The NES's 2A03 is an NMOS 6502 with the
+// decimal mode circuitry cut: D can be set and cleared but ADC/SBC stay binary.
+// The CMOS 65C02 adds instructions, turns the unofficial opcodes into NOPs and
+// sets N and Z from the decimal result at the cost of an extra cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CpuVariant {
+    #[default]
+    Ricoh2A03,
+    Nmos6502,
+    Cmos65C02,
+}
+
+impl CpuVariant {
+    pub fn has_decimal_mode(self) -> bool {
+        match self {
+            CpuVariant::Ricoh2A03 => false,
+            CpuVariant::Nmos6502 | CpuVariant::Cmos65C02 => true,
+        }
+    }
+
+    // Where JMP ($xxxx) fetches the high byte of its target. NMOS parts never
+    // carry into the pointer's high byte, so JMP ($10FF) reads $10FF and $1000;
+    // the 65C02 fixed that.
+    pub fn indirect_jump_hi(self, ptr: u16) -> u16 {
+        match self {
+            CpuVariant::Ricoh2A03 | CpuVariant::Nmos6502 => {
+                (ptr & 0xFF00) | (ptr as u8).wrapping_add(1) as u16
+            }
+            CpuVariant::Cmos65C02 => ptr.wrapping_add(1),
+        }
+    }
 
-pub struct CPU {
+    pub fn opcode(self, code: u8) -> &'static OpCode {
+        match self {
+            CpuVariant::Ricoh2A03 | CpuVariant::Nmos6502 => find_opcode(code),
+            CpuVariant::Cmos65C02 => find_cmos_opcode(code),
+        }
+    }
+}
+
+// How an instruction uses its operand. The dispatcher resolves the addressing
+// mode and does the memory access, so handlers only see values.
+pub enum Operation<M: Mem> {
+    Implied(fn(&mut CPU<M>)),
+    Stack(fn(&mut CPU<M>)),
+    Read(fn(&mut CPU<M>, u8)),
+    Write(fn(&mut CPU<M>) -> u8),
+    Modify(fn(&mut CPU<M>, u8) -> u8),
+    Branch(fn(&CPU<M>) -> bool),
+    Jump(fn(&mut CPU<M>, u16)),
+    // AHX/SHX/SHY/TAS: the returned value gets AND-ed with the base address high byte + 1
+    UnstableStore(fn(&mut CPU<M>) -> u8),
+    Jam,
+}
+
+impl<M: Mem> Clone for Operation<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: Mem> Copy for Operation<M> {}
+
+pub struct Instruction<M: Mem> {
+    pub opcode: &'static OpCode,
+    pub operation: Operation<M>,
+}
+
+impl<M: Mem> Clone for Instruction<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: Mem> Copy for Instruction<M> {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StepInfo {
+    // address and opcode of the executed instruction
+    pub counter: u16,
+    pub opcode: u8,
+    // includes the 7 cycles of an interrupt taken before the instruction
+    pub cycles: u64,
+    pub interrupt: Option<InterruptType>,
+}
+
+// What a run_until() callback wants after each instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunControl {
+    Continue,
+    Stop,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct CPU<M: Mem = Bus> {
     pub reg_a: u8,
     pub status: u8,
     pub program_counter: u16,
     pub reg_x: u8,
     pub reg_y: u8,
     pub stp: u8,
-    memory: [u8; 0xFFFF],
+    pub cycles: u64,
+    pub bus: M,
+    nmi_line: bool,
+    nmi_pending: bool,
+    irq_line: bool,
+    variant: CpuVariant,
+    pub unstable: UnstableOpcodes,
+    // when set, step() runs the instruction one tick() at a time so every
+    // bus access, dummy ones included, happens in hardware order
+    pub cycle_stepped: bool,
+    // report stack pointer wraparound to the tracer; execution is unaffected
+    pub diagnostics: bool,
+    decode_table: Box<[Instruction<M>; 256]>,
+    tracer: Option<Box<dyn TraceSink>>,
+    // address of the instruction or interrupt being executed, for diagnostics
+    instruction_counter: u16,
+    // cycle-stepped state: the instruction or interrupt sequence under way,
+    // and the start cycle and interrupt of the step it belongs to
+    in_flight: Option<InFlight<M>>,
+    step_start: Option<(u64, Option<InterruptType>)>,
+}
+
+impl<M: Mem> Mem for CPU<M> {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.bus.mem_read(addr)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.bus.mem_peek(addr)
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.bus.mem_write(addr, data);
+    }
+
+    fn clock(&mut self, cycles: u64) {
+        self.bus.clock(cycles);
+    }
+
+    fn irq(&self) -> bool {
+        self.bus.irq()
+    }
 }
 
-impl CPU {
-    pub fn new() -> Self {
+impl<M: Mem> CPU<M> {
+    pub fn new(bus: M) -> Self {
+        Self::with_variant(bus, CpuVariant::default())
+    }
+
+    pub fn with_variant(bus: M, variant: CpuVariant) -> Self {
         CPU {
+            // what power_on() leaves behind, short of the reset vector
             reg_a: 0,
-            status: 0,
+            status: StatusFlag::InterruptDisable as u8 | StatusFlag::Unused as u8,
             program_counter: 0,
             reg_x: 0,
             reg_y: 0,
-            stp: 0xff,
-            memory: [0; 0xFFFF],
+            stp: 0xfd,
+            cycles: 0,
+            bus,
+            nmi_line: false,
+            nmi_pending: false,
+            irq_line: false,
+            variant,
+            unstable: UnstableOpcodes::default(),
+            cycle_stepped: false,
+            diagnostics: false,
+            decode_table: Self::build_decode_table(variant),
+            tracer: None,
+            instruction_counter: 0,
+            in_flight: None,
+            step_start: None,
         }
     }
 
     // STACK COMMANDS START
 
-    fn push(&mut self, value: u8) -> Result<(), StackError> {
-        if self.stp == 0 {
-            return Err(StackError {
-                counter: self.program_counter,
-                err_msg: "Stack Overflow".to_owned(),
+    // The stack lives in page $01 and SP wraps inside it, as on the real
+    // chip. Wrapping is only reported, and only with stack_diagnostics on.
+    fn push(&mut self, value: u8) {
+        self.mem_write(STACK_OFFSET + self.stp as u16, value);
+        if self.stp == 0x00 {
+            self.diagnose(Diagnostic::StackOverflow {
+                counter: self.instruction_counter,
             });
         }
-        self.stp -= 1;
-        self.mem_write(STACK_OFFSET + self.stp as u16, value);
-        println!(
-            "VALUE PUSHED: {:#x} to {:#x}",
-            value,
-            STACK_OFFSET + self.stp as u16
-        );
-        Ok(())
+        self.stp = self.stp.wrapping_sub(1);
     }
 
-    fn push_u16(&mut self, value: u16) -> Result<(), StackError> {
-        println!("u16 PUSHED {:#x}", value);
-        print!("           ");
-        self.push((value >> 8) as u8)?;
-        print!("           ");
-        self.push((value & 0xFF) as u8)?;
-        Ok(())
+    fn push_u16(&mut self, value: u16) {
+        self.push((value >> 8) as u8);
+        self.push((value & 0xFF) as u8);
     }
 
-    fn pop(&mut self) -> Result<u8, StackError> {
-        if self.stp == 0x00 {
-            return Err(StackError {
-                counter: self.program_counter,
-                err_msg: "Stack Underflow".to_owned(),
+    fn pop(&mut self) -> u8 {
+        if self.stp == 0xFF {
+            self.diagnose(Diagnostic::StackUnderflow {
+                counter: self.instruction_counter,
             });
         }
-        let value = self.mem_read(STACK_OFFSET + self.stp as u16);
-        println!(
-            "VALUE POPPED: {:#x} from {:#x}",
-            value,
-            STACK_OFFSET + self.stp as u16
-        );
-        self.stp += 1;
-        Ok(value)
+        self.stp = self.stp.wrapping_add(1);
+        self.mem_read(STACK_OFFSET + self.stp as u16)
     }
 
-    fn pop_u16(&mut self) -> Result<u16, StackError> {
-        let lo = self.pop()? as u16;
-        let hi = self.pop()? as u16;
-        Ok((hi << 8) | lo)
+    fn pop_u16(&mut self) -> u16 {
+        let lo = self.pop() as u16;
+        let hi = self.pop() as u16;
+        (hi << 8) | lo
     }
 
-    // STACK COMMANDS START
+    // STACK COMMANDS END
 
     // INSTRUCTIONS START
 
-    fn lda(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        println!("     Value loaded: {:#x} from address: {:#x}", value, addr);
-        self.reg_a = value;
-        self.update_zero_and_negative_flags(self.reg_a);
+    fn lda(&mut self, value: u8) {
+        self.set_reg_a(value);
     }
 
-    fn ldx(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-
+    fn ldx(&mut self, value: u8) {
         self.reg_x = value;
         self.update_zero_and_negative_flags(self.reg_x);
     }
 
-    fn ldy(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-
+    fn ldy(&mut self, value: u8) {
         self.reg_y = value;
         self.update_zero_and_negative_flags(self.reg_y);
     }
@@ -118,8 +283,7 @@ impl CPU {
     }
 
     fn txa(&mut self) {
-        self.reg_a = self.reg_x;
-        self.update_zero_and_negative_flags(self.reg_a);
+        self.set_reg_a(self.reg_x);
     }
 
     fn txs(&mut self) {
@@ -127,290 +291,166 @@ impl CPU {
     }
 
     fn tya(&mut self) {
-        self.reg_a = self.reg_y;
-        self.update_zero_and_negative_flags(self.reg_a);
+        self.set_reg_a(self.reg_y);
     }
 
     fn inx(&mut self) {
-        if self.reg_x == 0xFF {
-            self.reg_x = 0;
-        } else {
-            self.reg_x += 1;
-        }
+        self.reg_x = self.reg_x.wrapping_add(1);
         self.update_zero_and_negative_flags(self.reg_x);
     }
 
     fn iny(&mut self) {
-        if self.reg_y == 0xFF {
-            self.reg_y = 0;
-        } else {
-            self.reg_y += 1;
-        }
+        self.reg_y = self.reg_y.wrapping_add(1);
         self.update_zero_and_negative_flags(self.reg_y);
     }
 
     fn dex(&mut self) {
-        if self.reg_x == 0x00 {
-            self.reg_x = 0b1111_1111;
-        } else {
-            self.reg_x -= 1;
-        }
+        self.reg_x = self.reg_x.wrapping_sub(1);
         self.update_zero_and_negative_flags(self.reg_x);
     }
 
     fn dey(&mut self) {
-        if self.reg_y == 0x00 {
-            self.reg_y = 0b1111_1111;
-        } else {
-            self.reg_y -= 1;
-        }
+        self.reg_y = self.reg_y.wrapping_sub(1);
         self.update_zero_and_negative_flags(self.reg_y);
     }
 
-    fn sta(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.mem_write(addr, self.reg_a);
+    fn sta(&mut self) -> u8 {
+        self.reg_a
     }
 
-    fn stx(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.mem_write(addr, self.reg_x);
+    fn stx(&mut self) -> u8 {
+        self.reg_x
     }
 
-    fn sty(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.mem_write(addr, self.reg_y);
+    fn sty(&mut self) -> u8 {
+        self.reg_y
     }
 
-    fn pla(&mut self) {
-        match self.pop() {
-            Ok(value) => {
-                self.reg_a = value;
-                self.update_zero_and_negative_flags(self.reg_a);
-            }
-            Err(e) => eprintln!("{}", e),
-        };
+    fn pha(&mut self) {
+        self.push(self.reg_a);
     }
 
-    fn plp(&mut self) {
-        match self.pop() {
-            Ok(value) => {
-                self.status = value;
-            }
-            Err(e) => eprintln!("{}", e),
-        };
+    fn php(&mut self) {
+        let flags = self.status | StatusFlag::Break as u8 | StatusFlag::Unused as u8;
+        self.push(flags);
     }
 
-    fn adc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        self.add_to_reg_a(value);
+    fn phx(&mut self) {
+        self.push(self.reg_x);
     }
 
-    fn sbc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        self.add_to_reg_a(((value as i8).wrapping_neg().wrapping_sub(1)) as u8);
+    fn phy(&mut self) {
+        self.push(self.reg_y);
     }
 
-    fn and(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        let result = value & self.reg_a;
-        self.set_reg_a(result);
-        self.update_zero_and_negative_flags(self.reg_a);
+    fn pla(&mut self) {
+        let value = self.pop();
+        self.set_reg_a(value);
     }
 
-    fn eor(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        let result = value ^ self.reg_a;
-        self.set_reg_a(result);
-        self.update_zero_and_negative_flags(self.reg_a);
+    fn plx(&mut self) {
+        self.reg_x = self.pop();
+        self.update_zero_and_negative_flags(self.reg_x);
     }
 
-    fn ora(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        let result = value | self.reg_a;
-        self.set_reg_a(result);
-        self.update_zero_and_negative_flags(self.reg_a);
+    fn ply(&mut self) {
+        self.reg_y = self.pop();
+        self.update_zero_and_negative_flags(self.reg_y);
     }
 
-    fn asl(&mut self, mode: &AddressingMode) {
-        let mut addr: u16 = 0;
-        let mut value = match mode {
-            AddressingMode::Immediate => self.reg_a,
-            _ => {
-                addr = self.get_operand_address(mode);
-                self.mem_read(addr)
-            }
-        };
+    fn plp(&mut self) {
+        let value = self.pop();
+        self.set_status_from_stack(value);
+    }
 
-        if value & 0b1000_0000 == 0 {
-            self.clear_flag(StatusFlag::Carry);
+    fn adc(&mut self, value: u8) {
+        if self.decimal_mode() {
+            self.add_to_reg_a_decimal(value);
         } else {
-            self.set_flag(StatusFlag::Carry);
+            self.add_to_reg_a(value);
         }
-
-        value = value << 1;
-        self.update_zero_and_negative_flags(value);
-
-        match mode {
-            AddressingMode::Immediate => {
-                self.set_reg_a(value);
-            }
-            _ => {
-                self.mem_write(addr, value);
-            }
-        };
     }
 
-    fn rol(&mut self, mode: &AddressingMode) {
-        let mut addr: u16 = 0;
-        let mut value = match mode {
-            AddressingMode::Immediate => self.reg_a,
-            _ => {
-                addr = self.get_operand_address(mode);
-                self.mem_read(addr)
-            }
-        };
-
-        if value & 0b1000_0000 == 0 {
-            value = value << 1;
-            value &= 0b1111_1110;
-            value += match self.check_flag(StatusFlag::Carry) {
-                true => 1,
-                false => 0,
-            };
-            self.update_zero_and_negative_flags(value);
-            self.clear_flag(StatusFlag::Carry);
+    fn sbc(&mut self, value: u8) {
+        if self.decimal_mode() {
+            self.sub_from_reg_a_decimal(value);
         } else {
-            value = value << 1;
-            value &= 0b1111_1110;
-            value += match self.check_flag(StatusFlag::Carry) {
-                true => 1,
-                false => 0,
-            };
-            self.update_zero_and_negative_flags(value);
-            self.set_flag(StatusFlag::Carry);
+            self.add_to_reg_a(!value);
         }
+    }
 
-        match mode {
-            AddressingMode::Immediate => {
-                self.set_reg_a(value);
-            }
-            _ => {
-                self.mem_write(addr, value);
-            }
-        };
+    fn and(&mut self, value: u8) {
+        self.set_reg_a(self.reg_a & value);
     }
 
-    fn ror(&mut self, mode: &AddressingMode) {
-        let mut addr: u16 = 0;
-        let mut value = match mode {
-            AddressingMode::Immediate => self.reg_a,
-            _ => {
-                addr = self.get_operand_address(mode);
-                self.mem_read(addr)
-            }
-        };
+    fn eor(&mut self, value: u8) {
+        self.set_reg_a(self.reg_a ^ value);
+    }
 
-        if value & 0b0000_0001 == 0 {
-            value = value >> 1;
-            value &= 0b0111_1111;
-            value |= match self.check_flag(StatusFlag::Carry) {
-                true => 0b1000_0000,
-                false => 0,
-            };
-            self.update_zero_and_negative_flags(value);
-            self.clear_flag(StatusFlag::Carry);
-        } else {
-            value = value >> 1;
-            value &= 0b0111_1111;
-            value |= match self.check_flag(StatusFlag::Carry) {
-                true => 0b1000_0000,
-                false => 0,
-            };
-            self.update_zero_and_negative_flags(value);
-            self.set_flag(StatusFlag::Carry);
-        }
+    fn ora(&mut self, value: u8) {
+        self.set_reg_a(self.reg_a | value);
+    }
 
-        match mode {
-            AddressingMode::Immediate => {
-                self.set_reg_a(value);
-            }
-            _ => {
-                self.mem_write(addr, value);
-            }
-        };
+    fn asl(&mut self, value: u8) -> u8 {
+        self.set_carry(value & 0b1000_0000 != 0);
+        let result = value << 1;
+        self.update_zero_and_negative_flags(result);
+        result
     }
 
-    fn lsr(&mut self, mode: &AddressingMode) {
-        let mut addr: u16 = 0;
-        let mut value = match mode {
-            AddressingMode::Immediate => self.reg_a,
-            _ => {
-                addr = self.get_operand_address(mode);
-                self.mem_read(addr)
-            }
-        };
+    fn lsr(&mut self, value: u8) -> u8 {
+        self.set_carry(value & 0b0000_0001 != 0);
+        let result = value >> 1;
+        self.update_zero_and_negative_flags(result);
+        result
+    }
 
-        if value & 0b0000_0001 == 0 {
-            self.clear_flag(StatusFlag::Carry);
-        } else {
-            self.set_flag(StatusFlag::Carry);
-        }
+    fn rol(&mut self, value: u8) -> u8 {
+        let carry_in = self.check_flag(StatusFlag::Carry) as u8;
+        self.set_carry(value & 0b1000_0000 != 0);
+        let result = (value << 1) | carry_in;
+        self.update_zero_and_negative_flags(result);
+        result
+    }
 
-        value = value >> 1;
-        self.update_zero_and_negative_flags(value);
+    fn ror(&mut self, value: u8) -> u8 {
+        let carry_in = self.check_flag(StatusFlag::Carry) as u8;
+        self.set_carry(value & 0b0000_0001 != 0);
+        let result = (value >> 1) | (carry_in << 7);
+        self.update_zero_and_negative_flags(result);
+        result
+    }
 
-        match mode {
-            AddressingMode::Immediate => {
-                self.set_reg_a(value);
-            }
-            _ => {
-                self.mem_write(addr, value);
-            }
-        };
+    fn inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.update_zero_and_negative_flags(result);
+        result
     }
 
-    fn cmp(&mut self, mode: &AddressingMode, reg: u8) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.update_zero_and_negative_flags(result);
+        result
+    }
 
-        if reg >= value {
-            self.set_flag(StatusFlag::Carry);
-            self.clear_flag(StatusFlag::Negative);
-        } else {
-            self.clear_flag(StatusFlag::Carry);
-            self.set_flag(StatusFlag::Negative);
-        }
+    fn cmp(&mut self, value: u8) {
+        self.compare(self.reg_a, value);
+    }
 
-        if reg == value {
-            self.set_flag(StatusFlag::Zero);
-        } else {
-            self.clear_flag(StatusFlag::Zero);
-        }
+    fn cpx(&mut self, value: u8) {
+        self.compare(self.reg_x, value);
     }
 
-    fn branch(&mut self, condition: bool) {
-        if condition {
-            let jump: i8 = self.mem_read(self.program_counter) as i8;
-            let jump_addr = self
-                .program_counter
-                .wrapping_add(1)
-                .wrapping_add(jump as u16);
+    fn cpy(&mut self, value: u8) {
+        self.compare(self.reg_y, value);
+    }
 
-            self.program_counter = jump_addr;
-        } else {
-            self.program_counter += 1;
-        }
+    fn compare(&mut self, reg: u8, value: u8) {
+        self.set_carry(reg >= value);
+        self.update_zero_and_negative_flags(reg.wrapping_sub(value));
     }
 
-    fn bit(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn bit(&mut self, value: u8) {
         if value & 0b1000_0000 != 0 {
             self.set_flag(StatusFlag::Negative);
         } else {
@@ -427,95 +467,247 @@ impl CPU {
             self.clear_flag(StatusFlag::Zero);
         }
     }
-    fn dec(&mut self, mode: &AddressingMode) {
-        let mut addr: u16 = self.get_operand_address(mode);
-        let mut value = self.mem_read(addr);
 
-        let result: u8 = match value {
-            0 => 0b1111_1111,
-            anything => anything - 1,
-        };
+    fn jmp(&mut self, addr: u16) {
+        self.program_counter = addr;
+    }
 
-        self.update_zero_and_negative_flags(result);
+    fn jsr(&mut self, addr: u16) {
+        // the pushed return address points at the last byte of the JSR
+        self.push_u16(self.program_counter.wrapping_sub(1));
+        self.program_counter = addr;
+    }
 
-        self.mem_write(addr, result);
+    fn rts(&mut self) {
+        self.program_counter = self.pop_u16().wrapping_add(1);
     }
 
-    fn inc(&mut self, mode: &AddressingMode) {
-        let mut addr: u16 = self.get_operand_address(mode);
-        let mut value = self.mem_read(addr);
+    fn rti(&mut self) {
+        let flags = self.pop();
+        self.set_status_from_stack(flags);
+        self.program_counter = self.pop_u16();
+    }
 
-        let result: u8 = match value {
-            0b1111_1111 => 0,
-            anything => anything + 1,
-        };
+    fn brk(&mut self) {
+        // BRK skips a padding byte, so the return address is opcode + 2
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.interrupt(BRK)
+    }
 
-        self.update_zero_and_negative_flags(result);
+    fn nop(&mut self) {}
 
-        self.mem_write(addr, result);
+    // CMOS INSTRUCTIONS START
+
+    fn stz(&mut self) -> u8 {
+        0
     }
 
-    fn jmp(&mut self, mode: &AddressingMode) {
-        self.program_counter = self.get_operand_address(mode);
+    fn tsb(&mut self, value: u8) -> u8 {
+        self.test_bits(value);
+        value | self.reg_a
     }
 
-    fn jsr(&mut self, mode: &AddressingMode) {
-        match self.push_u16(self.program_counter + 1) {
-            Err(e) => {
-                eprintln!("{}", e)
-            }
-            _ => {}
+    fn trb(&mut self, value: u8) -> u8 {
+        self.test_bits(value);
+        value & !self.reg_a
+    }
+
+    // BIT #imm only touches Z
+    fn bit_immediate(&mut self, value: u8) {
+        self.test_bits(value);
+    }
+
+    fn test_bits(&mut self, value: u8) {
+        if value & self.reg_a == 0 {
+            self.set_flag(StatusFlag::Zero);
+        } else {
+            self.clear_flag(StatusFlag::Zero);
         }
-        self.program_counter = self.get_operand_address(mode);
     }
-    fn rts(&mut self) {
-        self.program_counter = match self.pop_u16() {
-            Err(e) => {
-                eprintln!("{}", e);
-                self.brk();
-                0
-            }
-            Ok(counter) => counter,
-        };
+
+    // CMOS INSTRUCTIONS END
+
+    // UNOFFICIAL INSTRUCTIONS START
+
+    fn nop_read(&mut self, _value: u8) {}
+
+    fn lax(&mut self, value: u8) {
+        self.lda(value);
+        self.tax();
     }
 
-    fn rti(&mut self) {
-        self.status = match self.pop() {
-            Err(e) => {
-                eprintln!("{}", e);
-                self.brk();
-                0
-            }
-            Ok(flags) => flags,
-        };
-        self.program_counter = match self.pop_u16() {
-            Err(e) => {
-                eprintln!("{}", e);
-                self.brk();
-                0
-            }
-            Ok(counter) => counter,
-        };
+    fn sax(&mut self) -> u8 {
+        self.reg_a & self.reg_x
     }
 
-    fn brk(&mut self) {
-        match self.push_u16(self.program_counter + 1) {
-            Err(e) => {
-                eprintln!("{}", e)
-            }
-            _ => {}
-        }
-        match self.push(self.status) {
-            Err(e) => {
-                eprintln!("{}", e)
-            }
-            _ => {}
+    fn slo(&mut self, value: u8) -> u8 {
+        let result = self.asl(value);
+        self.ora(result);
+        result
+    }
+
+    fn rla(&mut self, value: u8) -> u8 {
+        let result = self.rol(value);
+        self.and(result);
+        result
+    }
+
+    fn sre(&mut self, value: u8) -> u8 {
+        let result = self.lsr(value);
+        self.eor(result);
+        result
+    }
+
+    fn rra(&mut self, value: u8) -> u8 {
+        let result = self.ror(value);
+        self.adc(result);
+        result
+    }
+
+    fn dcp(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.cmp(result);
+        result
+    }
+
+    fn isb(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.sbc(result);
+        result
+    }
+
+    fn anc(&mut self, value: u8) {
+        self.and(value);
+        self.set_carry(self.check_flag(StatusFlag::Negative));
+    }
+
+    fn alr(&mut self, value: u8) {
+        self.and(value);
+        self.reg_a = self.lsr(self.reg_a);
+    }
+
+    fn arr(&mut self, value: u8) {
+        let carry = self.check_flag(StatusFlag::Carry) as u8;
+        let result = ((self.reg_a & value) >> 1) | (carry << 7);
+        self.set_reg_a(result);
+
+        self.set_carry(result & 0b0100_0000 != 0);
+        if ((result >> 6) ^ (result >> 5)) & 1 != 0 {
+            self.set_flag(StatusFlag::Overflow);
+        } else {
+            self.clear_flag(StatusFlag::Overflow);
         }
-        self.set_flag(StatusFlag::Break);
     }
 
+    fn axs(&mut self, value: u8) {
+        let and = self.reg_a & self.reg_x;
+        self.set_carry(and >= value);
+        self.reg_x = and.wrapping_sub(value);
+        self.update_zero_and_negative_flags(self.reg_x);
+    }
+
+    fn las(&mut self, value: u8) {
+        let value = value & self.stp;
+        self.reg_a = value;
+        self.reg_x = value;
+        self.stp = value;
+        self.update_zero_and_negative_flags(value);
+    }
+
+    fn xaa(&mut self, value: u8) {
+        self.set_reg_a((self.reg_a | self.unstable.xaa_magic) & self.reg_x & value);
+    }
+
+    fn lxa(&mut self, value: u8) {
+        self.set_reg_a((self.reg_a | self.unstable.lxa_magic) & value);
+        self.reg_x = self.reg_a;
+    }
+
+    fn ahx(&mut self) -> u8 {
+        self.reg_a & self.reg_x
+    }
+
+    fn shx(&mut self) -> u8 {
+        self.reg_x
+    }
+
+    fn shy(&mut self) -> u8 {
+        self.reg_y
+    }
+
+    fn tas(&mut self) -> u8 {
+        self.stp = self.reg_a & self.reg_x;
+        self.stp
+    }
+
+    // UNOFFICIAL INSTRUCTIONS END
+
     // INSTRUCTIONS END
 
+    // INTERRUPTS START
+
+    // edge-triggered: call once per NMI, e.g. when the PPU enters vblank
+    pub fn trigger_nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    // for sources that drive the NMI line as a level; only a low-to-asserted
+    // transition requests an interrupt
+    pub fn set_nmi_line(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = asserted;
+    }
+
+    // level-triggered: the IRQ is taken on every instruction boundary while
+    // the line is asserted and the I flag is clear. The bus can hold it low
+    // too, wired-OR with this.
+    pub fn set_irq_line(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    fn pending_interrupt(&mut self) -> Option<Interrupt> {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            Some(NMI)
+        } else if (self.irq_line || self.bus.irq())
+            && !self.check_flag(StatusFlag::InterruptDisable)
+        {
+            Some(IRQ)
+        } else {
+            None
+        }
+    }
+
+    fn poll_interrupts(&mut self) -> Option<InterruptType> {
+        let interrupt = self.pending_interrupt()?;
+        self.interrupt(interrupt);
+        Some(interrupt.itype)
+    }
+
+    fn interrupt(&mut self, interrupt: Interrupt) {
+        self.push_u16(self.program_counter);
+        self.push(self.interrupt_flags(interrupt));
+        self.mask_interrupts();
+
+        self.cycles += interrupt.cpu_cycles as u64;
+        self.program_counter = self.mem_read_u16(interrupt.vector_addr);
+    }
+
+    fn interrupt_flags(&self, interrupt: Interrupt) -> u8 {
+        (self.status & !(StatusFlag::Break as u8)) | interrupt.b_flag_mask
+    }
+
+    fn mask_interrupts(&mut self) {
+        self.set_flag(StatusFlag::InterruptDisable);
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.clear_flag(StatusFlag::DecimalMode);
+        }
+    }
+
+    // INTERRUPTS END
+
     // FLAGS START
 
     pub fn set_flag(&mut self, flag: StatusFlag) {
@@ -530,6 +722,23 @@ impl CPU {
         self.status & (flag as u8) != 0
     }
 
+    fn set_carry(&mut self, carry: bool) {
+        if carry {
+            self.set_flag(StatusFlag::Carry);
+        } else {
+            self.clear_flag(StatusFlag::Carry);
+        }
+    }
+
+    // the B and unused bits only exist in pushed copies of the status
+    fn set_status_from_stack(&mut self, value: u8) {
+        self.status = (value & !(StatusFlag::Break as u8)) | StatusFlag::Unused as u8;
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.variant.has_decimal_mode() && self.check_flag(StatusFlag::DecimalMode)
+    }
+
     fn update_zero_and_negative_flags(&mut self, result: u8) {
         if result == 0 {
             self.set_flag(StatusFlag::Zero);
@@ -581,339 +790,528 @@ impl CPU {
         self.set_reg_a(result);
     }
 
+    // NMOS decimal ADC: Z comes from the binary sum, N and V from the sum after
+    // the low nibble is adjusted but before the high one is, C from the BCD result.
+    fn add_to_reg_a_decimal(&mut self, data: u8) {
+        let a = self.reg_a;
+        let carry_in = self.check_flag(StatusFlag::Carry) as u16;
+        let binary = (a as u16 + data as u16 + carry_in) as u8;
+
+        let mut lo = (a & 0x0F) as u16 + (data & 0x0F) as u16 + carry_in;
+        if lo >= 0x0A {
+            lo = ((lo + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = (a & 0xF0) as u16 + (data & 0xF0) as u16 + lo;
+
+        let signed = (a & 0xF0) as i8 as i16 + (data & 0xF0) as i8 as i16 + lo as i16;
+        if !(-128..=127).contains(&signed) {
+            self.set_flag(StatusFlag::Overflow);
+        } else {
+            self.clear_flag(StatusFlag::Overflow);
+        }
+
+        self.update_zero_and_negative_flags(binary);
+        if sum & 0x80 != 0 {
+            self.set_flag(StatusFlag::Negative);
+        } else {
+            self.clear_flag(StatusFlag::Negative);
+        }
+
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+        self.set_carry(sum >= 0x100);
+        self.reg_a = sum as u8;
+        self.fix_decimal_flags();
+    }
+
+    // NMOS decimal SBC: all flags are those of the binary subtraction, only
+    // the accumulator gets the BCD result. The 65C02 adjusts the nibbles in a
+    // different order, which only matters for invalid BCD operands.
+    fn sub_from_reg_a_decimal(&mut self, data: u8) {
+        let a = self.reg_a;
+        let borrow = 1 - self.check_flag(StatusFlag::Carry) as i16;
+
+        let lo = (a & 0x0F) as i16 - (data & 0x0F) as i16 - borrow;
+        let result = if self.variant == CpuVariant::Cmos65C02 {
+            let mut result = a as i16 - data as i16 - borrow;
+            if result < 0 {
+                result -= 0x60;
+            }
+            if lo < 0 {
+                result -= 0x06;
+            }
+            result
+        } else {
+            let lo = if lo < 0 { ((lo - 0x06) & 0x0F) - 0x10 } else { lo };
+            let mut result = (a & 0xF0) as i16 - (data & 0xF0) as i16 + lo;
+            if result < 0 {
+                result -= 0x60;
+            }
+            result
+        };
+
+        self.add_to_reg_a(!data);
+        self.reg_a = result as u8;
+        self.fix_decimal_flags();
+    }
+
+    // the 65C02 spends a cycle to make N and Z reflect the decimal result
+    fn fix_decimal_flags(&mut self) {
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.update_zero_and_negative_flags(self.reg_a);
+            self.cycles += 1;
+        }
+    }
+
     // REGISTERS END
 
     // OPERANDS START
 
-    fn get_operand_address(&mut self, mode: &AddressingMode) -> u16 {
-        match mode {
-            AddressingMode::Immediate => self.program_counter,
+    fn get_operand_address(&mut self, mode: &AddressingMode) -> Result<(u16, bool), CpuError> {
+        let operand = match mode {
+            AddressingMode::Immediate | AddressingMode::Relative => (self.program_counter, false),
 
-            AddressingMode::ZeroPage => self.mem_read(self.program_counter) as u16,
+            AddressingMode::ZeroPage => (self.mem_read(self.program_counter) as u16, false),
 
-            AddressingMode::Absolute => self.mem_read_u16(self.program_counter),
+            AddressingMode::Absolute => (self.mem_read_u16(self.program_counter), false),
 
             AddressingMode::ZeroPage_X => {
                 let pos = self.mem_read(self.program_counter);
-                let addr = pos.wrapping_add(self.reg_x) as u16;
-                addr
+                (pos.wrapping_add(self.reg_x) as u16, false)
             }
             AddressingMode::ZeroPage_Y => {
                 let pos = self.mem_read(self.program_counter);
-                let addr = pos.wrapping_add(self.reg_y) as u16;
-                addr
+                (pos.wrapping_add(self.reg_y) as u16, false)
             }
 
             AddressingMode::Absolute_X => {
                 let base = self.mem_read_u16(self.program_counter);
                 let addr = base.wrapping_add(self.reg_x as u16);
-                addr
+                (addr, page_cross(base, addr))
             }
             AddressingMode::Absolute_Y => {
                 let base = self.mem_read_u16(self.program_counter);
                 let addr = base.wrapping_add(self.reg_y as u16);
-                addr
+                (addr, page_cross(base, addr))
             }
             AddressingMode::Indirect => {
                 let ptr: u16 = self.mem_read_u16(self.program_counter);
-                let addr: u16 = self.mem_read_u16(ptr);
-                addr
+                let lo = self.mem_read(ptr);
+                let hi = self.mem_read(self.variant.indirect_jump_hi(ptr));
+                ((hi as u16) << 8 | (lo as u16), false)
             }
             AddressingMode::Indirect_X => {
                 let base = self.mem_read(self.program_counter);
 
-                let ptr: u8 = (base as u8).wrapping_add(self.reg_x);
+                let ptr: u8 = base.wrapping_add(self.reg_x);
                 let lo = self.mem_read(ptr as u16);
                 let hi = self.mem_read(ptr.wrapping_add(1) as u16);
-                (hi as u16) << 8 | (lo as u16)
+                ((hi as u16) << 8 | (lo as u16), false)
             }
             AddressingMode::Indirect_Y => {
                 let base = self.mem_read(self.program_counter);
 
                 let lo = self.mem_read(base as u16);
-                let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
+                let hi = self.mem_read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.reg_y as u16);
-                deref
+                (deref, page_cross(deref_base, deref))
+            }
+
+            AddressingMode::ZeroPage_Indirect => {
+                let ptr = self.mem_read(self.program_counter);
+                let lo = self.mem_read(ptr as u16);
+                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
+                ((hi as u16) << 8 | (lo as u16), false)
+            }
+            AddressingMode::Absolute_X_Indirect => {
+                let base = self.mem_read_u16(self.program_counter);
+                let ptr = base.wrapping_add(self.reg_x as u16);
+                (self.mem_read_u16(ptr), false)
             }
-            AddressingMode::Relative => 0,
 
-            AddressingMode::NoneAddressing => {
-                panic!("mode {:?} is not supported", mode);
+            AddressingMode::Accumulator | AddressingMode::NoneAddressing => {
+                return Err(CpuError::InvalidAddressing {
+                    mode: *mode,
+                    counter: self.program_counter,
+                });
             }
+        };
+        Ok(operand)
+    }
+
+    fn page_cross_penalty(&mut self, page_cross: bool) {
+        if page_cross {
+            self.cycles += 1;
         }
     }
 
     // OPERANDS END
 
-    // MEM START
-
-    pub fn mem_read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    // DECODE START
+
+    // Every variant's opcode table covers all 256 codes, so a code without a
+    // handler is a bug in the tables rather than something a program can hit.
+    fn build_decode_table(variant: CpuVariant) -> Box<[Instruction<M>; 256]> {
+        Box::new(std::array::from_fn(|code| {
+            let opcode = variant.opcode(code as u8);
+            let operation = Self::operation_for(opcode)
+                .unwrap_or_else(|| panic!("no handler for {} ({:#04x})", opcode.name, code));
+            Instruction { opcode, operation }
+        }))
     }
 
-    pub fn mem_write(&mut self, addr: u16, data: u8) {
-        self.memory[addr as usize] = data;
+    fn operation_for(opcode: &OpCode) -> Option<Operation<M>> {
+        use Operation::*;
+
+        let operation = match opcode.name {
+            "LDA" => Read(Self::lda),
+            "LDX" => Read(Self::ldx),
+            "LDY" => Read(Self::ldy),
+            "STA" => Write(Self::sta),
+            "STX" => Write(Self::stx),
+            "STY" => Write(Self::sty),
+            "TAX" => Implied(Self::tax),
+            "TAY" => Implied(Self::tay),
+            "TSX" => Implied(Self::tsx),
+            "TXA" => Implied(Self::txa),
+            "TXS" => Implied(Self::txs),
+            "TYA" => Implied(Self::tya),
+            "PHA" => Stack(Self::pha),
+            "PHP" => Stack(Self::php),
+            "PLA" => Stack(Self::pla),
+            "PLP" => Stack(Self::plp),
+            "INX" => Implied(Self::inx),
+            "INY" => Implied(Self::iny),
+            "DEX" => Implied(Self::dex),
+            "DEY" => Implied(Self::dey),
+            "CLC" => Implied(|cpu| cpu.clear_flag(StatusFlag::Carry)),
+            "SEC" => Implied(|cpu| cpu.set_flag(StatusFlag::Carry)),
+            "CLD" => Implied(|cpu| cpu.clear_flag(StatusFlag::DecimalMode)),
+            "SED" => Implied(|cpu| cpu.set_flag(StatusFlag::DecimalMode)),
+            "CLI" => Implied(|cpu| cpu.clear_flag(StatusFlag::InterruptDisable)),
+            "SEI" => Implied(|cpu| cpu.set_flag(StatusFlag::InterruptDisable)),
+            "CLV" => Implied(|cpu| cpu.clear_flag(StatusFlag::Overflow)),
+            "ADC" => Read(Self::adc),
+            "SBC" | "*SBC" => Read(Self::sbc),
+            "AND" => Read(Self::and),
+            "EOR" => Read(Self::eor),
+            "ORA" => Read(Self::ora),
+            "ASL" => Modify(Self::asl),
+            "LSR" => Modify(Self::lsr),
+            "ROL" => Modify(Self::rol),
+            "ROR" => Modify(Self::ror),
+            "INC" => Modify(Self::inc),
+            "DEC" => Modify(Self::dec),
+            "BIT" if opcode.add_mode == AddressingMode::Immediate => Read(Self::bit_immediate),
+            "BIT" => Read(Self::bit),
+            "CMP" => Read(Self::cmp),
+            "CPX" => Read(Self::cpx),
+            "CPY" => Read(Self::cpy),
+            "BCC" => Branch(|cpu| !cpu.check_flag(StatusFlag::Carry)),
+            "BCS" => Branch(|cpu| cpu.check_flag(StatusFlag::Carry)),
+            "BEQ" => Branch(|cpu| cpu.check_flag(StatusFlag::Zero)),
+            "BNE" => Branch(|cpu| !cpu.check_flag(StatusFlag::Zero)),
+            "BMI" => Branch(|cpu| cpu.check_flag(StatusFlag::Negative)),
+            "BPL" => Branch(|cpu| !cpu.check_flag(StatusFlag::Negative)),
+            "BVS" => Branch(|cpu| cpu.check_flag(StatusFlag::Overflow)),
+            "BVC" => Branch(|cpu| !cpu.check_flag(StatusFlag::Overflow)),
+            "JMP" => Jump(Self::jmp),
+            "JSR" => Jump(Self::jsr),
+            "RTS" => Stack(Self::rts),
+            "RTI" => Stack(Self::rti),
+            "BRK" => Stack(Self::brk),
+            "NOP" => Implied(Self::nop),
+
+            "BRA" => Branch(|_| true),
+            "PHX" => Stack(Self::phx),
+            "PHY" => Stack(Self::phy),
+            "PLX" => Stack(Self::plx),
+            "PLY" => Stack(Self::ply),
+            "STZ" => Write(Self::stz),
+            "TSB" => Modify(Self::tsb),
+            "TRB" => Modify(Self::trb),
+
+            "*NOP" => Read(Self::nop_read),
+            "*JAM" => Jam,
+            "*LAX" => Read(Self::lax),
+            "*SAX" => Write(Self::sax),
+            "*SLO" => Modify(Self::slo),
+            "*RLA" => Modify(Self::rla),
+            "*SRE" => Modify(Self::sre),
+            "*RRA" => Modify(Self::rra),
+            "*DCP" => Modify(Self::dcp),
+            "*ISB" => Modify(Self::isb),
+            "*ANC" => Read(Self::anc),
+            "*ALR" => Read(Self::alr),
+            "*ARR" => Read(Self::arr),
+            "*AXS" => Read(Self::axs),
+            "*LAS" => Read(Self::las),
+            "*XAA" => Read(Self::xaa),
+            "*LXA" => Read(Self::lxa),
+            "*AHX" => UnstableStore(Self::ahx),
+            "*SHX" => UnstableStore(Self::shx),
+            "*SHY" => UnstableStore(Self::shy),
+            "*TAS" => UnstableStore(Self::tas),
+            _ => return None,
+        };
+        Some(operation)
     }
 
-    fn mem_read_u16(&mut self, pos: u16) -> u16 {
-        let lo = self.mem_read(pos) as u16;
-        let hi = self.mem_read(pos + 1) as u16;
-        (hi << 8) | (lo as u16)
-    }
+    fn execute(&mut self, instruction: Instruction<M>) -> Result<(), CpuError> {
+        let op = instruction.opcode;
+        let mode = &op.add_mode;
+        let next_instruction = self.program_counter.wrapping_add(op.bytes as u16 - 1);
+        self.cycles += op.cycles as u64;
 
-    fn mem_write_u16(&mut self, pos: u16, data: u16) {
-        let hi = (data >> 8) as u8;
-        let lo = (data & 0xff) as u8;
-        self.mem_write(pos, lo);
-        self.mem_write(pos + 1, hi);
+        match instruction.operation {
+            Operation::Implied(handler) => {
+                self.program_counter = next_instruction;
+                handler(self);
+            }
+            Operation::Stack(handler) => {
+                self.program_counter = next_instruction;
+                handler(self);
+            }
+            Operation::Read(handler) => {
+                // single-byte unofficial NOPs are the only reads without an operand
+                let value = if let AddressingMode::NoneAddressing = mode {
+                    0
+                } else {
+                    let (addr, page_cross) = self.get_operand_address(mode)?;
+                    self.page_cross_penalty(page_cross);
+                    self.mem_read(addr)
+                };
+                self.program_counter = next_instruction;
+                handler(self, value);
+            }
+            Operation::Write(handler) => {
+                let (addr, _) = self.get_operand_address(mode)?;
+                self.program_counter = next_instruction;
+                let value = handler(self);
+                self.mem_write(addr, value);
+            }
+            Operation::Modify(handler) => {
+                if let AddressingMode::Accumulator = mode {
+                    self.program_counter = next_instruction;
+                    self.reg_a = handler(self, self.reg_a);
+                } else {
+                    let (addr, _) = self.get_operand_address(mode)?;
+                    self.program_counter = next_instruction;
+                    let value = self.mem_read(addr);
+                    // NMOS writes the unmodified value back, the 65C02 reads it again
+                    if self.variant == CpuVariant::Cmos65C02 {
+                        self.mem_read(addr);
+                    } else {
+                        self.mem_write(addr, value);
+                    }
+                    let result = handler(self, value);
+                    self.mem_write(addr, result);
+                }
+            }
+            Operation::Branch(condition) => {
+                let jump = self.mem_read(self.program_counter) as i8;
+                self.program_counter = next_instruction;
+                if condition(self) {
+                    let jump_addr = self.program_counter.wrapping_add(jump as u16);
+
+                    self.cycles += 1;
+                    if page_cross(self.program_counter, jump_addr) {
+                        self.cycles += 1;
+                    }
+
+                    self.program_counter = jump_addr;
+                }
+            }
+            Operation::Jump(handler) => {
+                let (addr, _) = self.get_operand_address(mode)?;
+                self.program_counter = next_instruction;
+                handler(self, addr);
+            }
+            Operation::UnstableStore(handler) => {
+                let (mut addr, page_cross) = self.get_operand_address(mode)?;
+                let index = match mode {
+                    AddressingMode::Absolute_X => self.reg_x,
+                    _ => self.reg_y,
+                };
+                let base = addr.wrapping_sub(index as u16);
+                self.program_counter = next_instruction;
+
+                let value = handler(self) & ((base >> 8) as u8).wrapping_add(1);
+                if page_cross && self.unstable.sh_page_cross_corrupts_address {
+                    addr = (value as u16) << 8 | (addr & 0x00FF);
+                }
+                self.mem_write(addr, value);
+            }
+            Operation::Jam => {
+                return Err(CpuError::Jam {
+                    opcode: op.code,
+                    counter: self.program_counter.wrapping_sub(1),
+                });
+            }
+        }
+        Ok(())
     }
 
-    // MEM END
+    // DECODE END
 
     // CONTROL START
 
-    pub fn reset(&mut self) {
+    pub fn variant(&self) -> CpuVariant {
+        self.variant
+    }
+
+    // tracing is off until a sink is installed
+    pub fn set_tracer(&mut self, sink: impl TraceSink + 'static) {
+        self.tracer = Some(Box::new(sink));
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.tracer.take()
+    }
+
+    // Cold start: the bus powers up (RAM gets its power-on pattern), the
+    // registers are cleared and the reset sequence runs from SP = $00, which
+    // leaves SP = $FD and P = $24 after 7 cycles.
+    pub fn power_on(&mut self) {
+        self.bus.power_on();
         self.reg_a = 0;
         self.reg_x = 0;
-        self.status = 0;
-        self.stp = 0xff;
+        self.reg_y = 0;
+        self.status = StatusFlag::Unused as u8;
+        self.stp = 0;
+        self.cycles = 0;
+        self.nmi_line = false;
+        self.irq_line = false;
+        self.reset_sequence();
+    }
+
+    // The reset button: registers and RAM keep their contents, SP drops by 3
+    // and I is set.
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.reset_sequence();
+    }
 
-        self.program_counter = self.mem_read_u16(0xFFFC);
+    // Reset is an interrupt whose three pushes are turned into reads, so SP
+    // still moves while the stack is left alone.
+    fn reset_sequence(&mut self) {
+        self.nmi_pending = false;
+        self.in_flight = None;
+        self.step_start = None;
+
+        self.mem_read(self.program_counter);
+        self.mem_read(self.program_counter);
+        for _ in 0..3 {
+            self.mem_read(STACK_OFFSET + self.stp as u16);
+            self.stp = self.stp.wrapping_sub(1);
+        }
+        self.mask_interrupts();
+        self.program_counter = self.mem_read_u16(RESET_VECTOR);
+        self.cycles += 7;
+        self.bus.clock(7);
     }
 
     pub fn load(&mut self, program: Vec<u8>) {
-        self.memory[0x0600..(0x0600 + program.len())].copy_from_slice(&program[..]);
-        self.mem_write_u16(0xFFFC, 0x0600);
+        for (i, byte) in program.iter().enumerate() {
+            self.mem_write(0x0600 + i as u16, *byte);
+        }
+        self.mem_write_u16(RESET_VECTOR, 0x0600);
+        self.program_counter = 0x0600;
     }
 
-    pub fn load_and_run(&mut self, program: Vec<u8>) {
+    pub fn load_and_run(&mut self, program: Vec<u8>) -> Result<(), CpuError> {
+        self.power_on();
         self.load(program);
-        self.reset();
         self.run()
     }
 
-    pub fn run(&mut self) {
-        self.run_with_callback(|_| {});
+    pub fn run(&mut self) -> Result<(), CpuError> {
+        self.run_with_callback(|_| {})
     }
 
-    pub fn run_with_callback<F>(&mut self, mut callback: F)
+    pub fn run_with_callback<F>(&mut self, mut callback: F) -> Result<(), CpuError>
     where
-        F: FnMut(&mut CPU),
+        F: FnMut(&mut CPU<M>),
+    {
+        self.run_until(|cpu, _| {
+            callback(cpu);
+            RunControl::Continue
+        })?;
+        Ok(())
+    }
+
+    // Steps until the callback, called after every instruction, asks to stop.
+    // Returns the last instruction executed.
+    pub fn run_until<F>(&mut self, mut callback: F) -> Result<StepInfo, CpuError>
+    where
+        F: FnMut(&mut CPU<M>, &StepInfo) -> RunControl,
     {
         loop {
-            let opcode_val = self.mem_read(self.program_counter);
-            let counter = self.program_counter;
-            print!(
-                "NEW COMMAND at: {:#x} ({} in dec), code: {:#x}, or: ",
-                counter, counter, opcode_val
-            );
-            self.program_counter += 1;
-            let op = find_opcode(opcode_val)
-                .expect(&format!("Unknown opcode {:#x}", opcode_val).to_owned());
-            println!("{}", op.name);
-
-            match op.name {
-                "LDA" => {
-                    self.lda(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "LDX" => {
-                    self.ldx(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "LDY" => {
-                    self.ldy(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "STA" => {
-                    self.sta(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "STX" => {
-                    self.stx(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "STY" => {
-                    self.sty(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "PLA" => {
-                    self.pla();
-                }
-                "PLP" => {
-                    self.plp();
-                }
-                "PHA" => match self.push(self.reg_a) {
-                    Err(e) => {
-                        eprintln!("{}", e)
-                    }
-                    _ => {}
-                },
-                "PHP" => match self.push(self.status) {
-                    Err(e) => {
-                        eprintln!("{}", e)
-                    }
-                    _ => {}
-                },
-                "TAX" => {
-                    self.tax();
-                }
-                "TAY" => {
-                    self.tay();
-                }
-                "TSX" => {
-                    self.tsx();
-                }
-                "TXA" => {
-                    self.txa();
-                }
-                "TXS" => {
-                    self.txs();
-                }
-                "TYA" => {
-                    self.tya();
-                }
-                "INX" => {
-                    self.inx();
-                }
-                "INY" => {
-                    self.iny();
-                }
-                "DEX" => {
-                    self.dex();
-                }
-                "DEY" => {
-                    self.dey();
-                }
-                "CLC" => {
-                    self.clear_flag(StatusFlag::Carry);
-                }
-                "SEC" => {
-                    self.set_flag(StatusFlag::Carry);
-                }
-                "CLD" => {
-                    self.clear_flag(StatusFlag::DecimalMode);
-                }
-                "SED" => {
-                    self.set_flag(StatusFlag::DecimalMode);
-                }
-                "CLI" => {
-                    self.clear_flag(StatusFlag::InterruptDisable);
-                }
-                "SEI" => {
-                    self.set_flag(StatusFlag::InterruptDisable);
-                }
-                "CLV" => {
-                    self.clear_flag(StatusFlag::Overflow);
-                }
-                "ADC" => {
-                    self.adc(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "SBC" => {
-                    self.sbc(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "AND" => {
-                    self.and(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "EOR" => {
-                    self.eor(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "ORA" => {
-                    self.ora(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "ASL" => {
-                    self.asl(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "ROL" => {
-                    self.rol(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "LSR" => {
-                    self.lsr(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "ROR" => {
-                    self.ror(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "BCC" => {
-                    self.branch(!self.check_flag(StatusFlag::Carry));
-                }
-                "BCS" => {
-                    self.branch(self.check_flag(StatusFlag::Carry));
-                }
-                "BEQ" => {
-                    self.branch(self.check_flag(StatusFlag::Zero));
-                }
-                "BNE" => {
-                    self.branch(!self.check_flag(StatusFlag::Zero));
-                }
-                "BMI" => {
-                    self.branch(self.check_flag(StatusFlag::Negative));
-                }
-                "BPL" => {
-                    self.branch(!self.check_flag(StatusFlag::Negative));
-                }
-                "BVS" => {
-                    self.branch(self.check_flag(StatusFlag::Overflow));
-                }
-                "BVC" => {
-                    self.branch(!self.check_flag(StatusFlag::Overflow));
-                }
-                "BIT" => {
-                    self.bit(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "CMP" => {
-                    self.cmp(&op.add_mode, self.reg_a);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "CPX" => {
-                    self.cmp(&op.add_mode, self.reg_x);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "CPY" => {
-                    self.cmp(&op.add_mode, self.reg_y);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "INC" => {
-                    self.inc(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "DEC" => {
-                    self.dec(&op.add_mode);
-                    self.program_counter += op.bytes as u16 - 1;
-                }
-                "JMP" => {
-                    self.jmp(&op.add_mode);
-                }
-                "JSR" => {
-                    self.jsr(&op.add_mode);
-                }
-                "RTS" => {
-                    self.rts();
-                    self.program_counter += 1;
-                }
-                "RTI" => {
-                    self.rti();
-                }
-                "NOP" => {
-                    continue;
-                }
-                "BRK" => {
-                    self.brk();
-                    return;
+            let info = self.step()?;
+            if callback(self, &info) == RunControl::Stop {
+                return Ok(info);
+            }
+        }
+    }
+
+    // Executes one instruction, entering a pending interrupt first. On error
+    // the program counter is left on the faulting instruction.
+    pub fn step(&mut self) -> Result<StepInfo, CpuError> {
+        if self.cycle_stepped || self.step_start.is_some() {
+            loop {
+                if let Some(info) = self.tick()? {
+                    return Ok(info);
                 }
-                _ => {}
             }
-            callback(self);
+        }
+
+        let start_cycles = self.cycles;
+        self.instruction_counter = self.program_counter;
+        let interrupt = self.poll_interrupts();
+        self.trace_instruction();
+
+        let counter = self.program_counter;
+        self.instruction_counter = counter;
+        let opcode = self.mem_read(counter);
+        let instruction = self.decode_table[opcode as usize];
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        let result = self.execute(instruction);
+        self.bus.clock(self.cycles - start_cycles);
+        if let Err(e) = result {
+            self.program_counter = counter;
+            return Err(e);
+        }
+
+        Ok(StepInfo {
+            counter,
+            opcode,
+            cycles: self.cycles - start_cycles,
+            interrupt,
+        })
+    }
+
+    fn diagnose(&mut self, diagnostic: Diagnostic) {
+        if !self.diagnostics {
+            return;
+        }
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.diagnostic(&diagnostic);
         }
     }
+
+    fn trace_instruction(&mut self) {
+        if self.tracer.is_some() {
+            let entry = trace::trace(self);
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.trace(&entry);
+            }
+        }
+    }
+}
+
+fn page_cross(addr1: u16, addr2: u16) -> bool {
+    addr1 & 0xFF00 != addr2 & 0xFF00
 }
@@ -0,0 +1,536 @@
+// Cycle-stepped execution. tick() does exactly one bus access, in the order
+// the 6502 does them: dummy reads while an indexed address is fixed up, the
+// dummy write of read-modify-write instructions, the stack reads before a
+// pull. The instruction handlers are the same value-based ones step() uses;
+// only the bus traffic around them is spread over cycles.
+
+use super::{CPU, CpuVariant, Instruction, Operation, StepInfo, page_cross};
+use crate::constants::{AddressingMode, BRK, CpuError, Interrupt, STACK_OFFSET};
+use crate::mem::Mem;
+
+enum Work<M: Mem> {
+    Instruction(Instruction<M>),
+    // an NMI or IRQ sequence, which takes the place of an opcode fetch
+    Interrupt(Interrupt),
+}
+
+impl<M: Mem> Clone for Work<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: Mem> Copy for Work<M> {}
+
+pub(super) struct InFlight<M: Mem> {
+    work: Work<M>,
+    counter: u16,
+    // cycles done after the opcode fetch
+    cycle: u8,
+    // cycles done after the effective address became known
+    tail: u8,
+    resolved: bool,
+    base: u16,
+    addr: u16,
+    // indexed accesses first read from the address whose high byte has not
+    // been fixed up yet; reads skip it when no page was crossed
+    dummy_addr: Option<u16>,
+    value: u8,
+    // cycles a handler added on top of the table, e.g. 65C02 decimal mode
+    extra: u64,
+}
+
+impl<M: Mem> InFlight<M> {
+    fn new(work: Work<M>, counter: u16) -> Self {
+        InFlight {
+            work,
+            counter,
+            cycle: 0,
+            tail: 0,
+            resolved: false,
+            base: 0,
+            addr: 0,
+            dummy_addr: None,
+            value: 0,
+            extra: 0,
+        }
+    }
+}
+
+impl<M: Mem> CPU<M> {
+    // Advances one CPU cycle. Returns the step info on the cycle that
+    // completes an instruction, None while one is still under way.
+    pub fn tick(&mut self) -> Result<Option<StepInfo>, CpuError> {
+        let start_cycles = self.cycles;
+        let result = self.advance();
+        self.bus.clock(self.cycles - start_cycles);
+        result
+    }
+
+    fn advance(&mut self) -> Result<Option<StepInfo>, CpuError> {
+        let Some(mut flight) = self.in_flight.take() else {
+            return self.begin_instruction();
+        };
+
+        self.cycles += 1;
+        flight.cycle += 1;
+        let done = match flight.work {
+            Work::Interrupt(interrupt) => self.interrupt_cycle(&mut flight, interrupt),
+            Work::Instruction(instruction) => self.instruction_cycle(&mut flight, instruction),
+        };
+        if done {
+            return Ok(self.finish(flight));
+        }
+        self.in_flight = Some(flight);
+        Ok(None)
+    }
+
+    // Opcode fetch, or the first cycle of an interrupt sequence when one is
+    // pending at the start of a step.
+    fn begin_instruction(&mut self) -> Result<Option<StepInfo>, CpuError> {
+        let counter = self.program_counter;
+        self.instruction_counter = counter;
+        if self.step_start.is_none() {
+            self.step_start = Some((self.cycles, None));
+            if let Some(interrupt) = self.pending_interrupt() {
+                self.step_start = Some((self.cycles, Some(interrupt.itype)));
+                self.cycles += 1;
+                self.mem_read(counter);
+                self.in_flight = Some(InFlight::new(Work::Interrupt(interrupt), counter));
+                return Ok(None);
+            }
+        }
+
+        self.trace_instruction();
+        let opcode = self.mem_read(counter);
+        let instruction = self.decode_table[opcode as usize];
+        if let Operation::Jam = instruction.operation {
+            self.step_start = None;
+            return Err(CpuError::Jam { opcode, counter });
+        }
+
+        self.cycles += 1;
+        self.program_counter = counter.wrapping_add(1);
+        let flight = InFlight::new(Work::Instruction(instruction), counter);
+        // the 65C02's one-cycle NOPs are done with the fetch
+        if instruction.opcode.cycles == 1 {
+            return Ok(self.finish(flight));
+        }
+        self.in_flight = Some(flight);
+        Ok(None)
+    }
+
+    fn finish(&mut self, flight: InFlight<M>) -> Option<StepInfo> {
+        let Work::Instruction(instruction) = flight.work else {
+            // the handler's first instruction belongs to the same step
+            return None;
+        };
+        let (start_cycles, interrupt) = self.step_start.take().unwrap_or((self.cycles, None));
+        Some(StepInfo {
+            counter: flight.counter,
+            opcode: instruction.opcode.code,
+            cycles: self.cycles - start_cycles,
+            interrupt,
+        })
+    }
+
+    fn fetch_operand(&mut self) -> u8 {
+        let value = self.mem_read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        value
+    }
+
+    // BRK, NMI and IRQ: push PC and P, then load PC from the vector
+    fn interrupt_cycle(
+        &mut self,
+        flight: &mut InFlight<M>,
+        interrupt: Interrupt,
+    ) -> bool {
+        match flight.cycle {
+            1 => {
+                // BRK skips its padding byte, hardware interrupts re-read it
+                self.mem_read(self.program_counter);
+                if interrupt.itype == BRK.itype {
+                    self.program_counter = self.program_counter.wrapping_add(1);
+                }
+            }
+            2 => self.push((self.program_counter >> 8) as u8),
+            3 => self.push(self.program_counter as u8),
+            4 => {
+                self.push(self.interrupt_flags(interrupt));
+                self.mask_interrupts();
+            }
+            5 => flight.value = self.mem_read(interrupt.vector_addr),
+            _ => {
+                let hi = self.mem_read(interrupt.vector_addr.wrapping_add(1));
+                self.program_counter = (hi as u16) << 8 | flight.value as u16;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn instruction_cycle(
+        &mut self,
+        flight: &mut InFlight<M>,
+        instruction: Instruction<M>,
+    ) -> bool {
+        let op = instruction.opcode;
+        match instruction.operation {
+            Operation::Implied(handler) => {
+                self.mem_read(self.program_counter);
+                if flight.cycle + 1 < op.cycles {
+                    return false;
+                }
+                self.program_counter = self.program_counter.wrapping_add(op.bytes as u16 - 1);
+                handler(self);
+                true
+            }
+            Operation::Stack(handler) => self.stack_cycle(flight, op.name, handler),
+            Operation::Branch(condition) => self.branch_cycle(flight, condition),
+            Operation::Jump(_) if op.name == "JSR" => self.jsr_cycle(flight),
+            Operation::Jam => true,
+            operation => {
+                if !flight.resolved {
+                    let fixup = !matches!(operation, Operation::Read(_));
+                    if self.address_cycle(flight, op.add_mode, fixup) {
+                        if let (true, Operation::Jump(handler)) = (flight.resolved, operation) {
+                            handler(self, flight.addr);
+                            return true;
+                        }
+                        return false;
+                    }
+                }
+                self.operand_cycle(flight, instruction)
+            }
+        }
+    }
+
+    // Works out the effective address one bus access at a time. Returns
+    // false when the mode needs no access, so the operand cycle can run in
+    // the same tick.
+    fn address_cycle(
+        &mut self,
+        flight: &mut InFlight<M>,
+        mode: AddressingMode,
+        fixup: bool,
+    ) -> bool {
+        let t = flight.cycle;
+        match mode {
+            AddressingMode::Immediate => {
+                flight.addr = self.program_counter;
+                self.program_counter = self.program_counter.wrapping_add(1);
+                flight.resolved = true;
+                return false;
+            }
+            AddressingMode::Accumulator
+            | AddressingMode::NoneAddressing
+            | AddressingMode::Relative => {
+                flight.resolved = true;
+                return false;
+            }
+            AddressingMode::ZeroPage => {
+                flight.addr = self.fetch_operand() as u16;
+                flight.resolved = true;
+            }
+            AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+                if t == 1 {
+                    flight.base = self.fetch_operand() as u16;
+                } else {
+                    self.mem_read(flight.base);
+                    let index = match mode {
+                        AddressingMode::ZeroPage_X => self.reg_x,
+                        _ => self.reg_y,
+                    };
+                    flight.addr = (flight.base as u8).wrapping_add(index) as u16;
+                    flight.resolved = true;
+                }
+            }
+            AddressingMode::Absolute => {
+                if t == 1 {
+                    flight.base = self.fetch_operand() as u16;
+                } else {
+                    flight.addr = (self.fetch_operand() as u16) << 8 | flight.base;
+                    flight.resolved = true;
+                }
+            }
+            AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+                if t == 1 {
+                    flight.base = self.fetch_operand() as u16;
+                } else {
+                    let base = (self.fetch_operand() as u16) << 8 | flight.base;
+                    let index = match mode {
+                        AddressingMode::Absolute_X => self.reg_x,
+                        _ => self.reg_y,
+                    };
+                    Self::index(flight, base, index, fixup);
+                }
+            }
+            AddressingMode::Indirect => {
+                // the 65C02 spends an extra cycle on JMP ($xxxx)
+                let cmos = self.variant == CpuVariant::Cmos65C02;
+                match (t, cmos) {
+                    (1, _) => flight.base = self.fetch_operand() as u16,
+                    (2, _) => flight.base |= (self.fetch_operand() as u16) << 8,
+                    (3, true) => {
+                        self.mem_read(self.program_counter.wrapping_sub(1));
+                    }
+                    (3, false) | (4, true) => flight.value = self.mem_read(flight.base),
+                    _ => {
+                        let hi = self.mem_read(self.variant.indirect_jump_hi(flight.base));
+                        flight.addr = (hi as u16) << 8 | flight.value as u16;
+                        flight.resolved = true;
+                    }
+                }
+            }
+            AddressingMode::Absolute_X_Indirect => match t {
+                1 => flight.base = self.fetch_operand() as u16,
+                2 => flight.base |= (self.fetch_operand() as u16) << 8,
+                3 => {
+                    self.mem_read(self.program_counter.wrapping_sub(1));
+                    flight.base = flight.base.wrapping_add(self.reg_x as u16);
+                }
+                4 => flight.value = self.mem_read(flight.base),
+                _ => {
+                    let hi = self.mem_read(flight.base.wrapping_add(1));
+                    flight.addr = (hi as u16) << 8 | flight.value as u16;
+                    flight.resolved = true;
+                }
+            },
+            AddressingMode::Indirect_X => match t {
+                1 => flight.base = self.fetch_operand() as u16,
+                2 => {
+                    self.mem_read(flight.base);
+                    flight.base = (flight.base as u8).wrapping_add(self.reg_x) as u16;
+                }
+                3 => flight.value = self.mem_read(flight.base),
+                _ => {
+                    let hi = self.mem_read((flight.base as u8).wrapping_add(1) as u16);
+                    flight.addr = (hi as u16) << 8 | flight.value as u16;
+                    flight.resolved = true;
+                }
+            },
+            AddressingMode::Indirect_Y => match t {
+                1 => flight.base = self.fetch_operand() as u16,
+                2 => flight.value = self.mem_read(flight.base),
+                _ => {
+                    let hi = self.mem_read((flight.base as u8).wrapping_add(1) as u16);
+                    let base = (hi as u16) << 8 | flight.value as u16;
+                    Self::index(flight, base, self.reg_y, fixup);
+                }
+            },
+            AddressingMode::ZeroPage_Indirect => match t {
+                1 => flight.base = self.fetch_operand() as u16,
+                2 => flight.value = self.mem_read(flight.base),
+                _ => {
+                    let hi = self.mem_read((flight.base as u8).wrapping_add(1) as u16);
+                    flight.addr = (hi as u16) << 8 | flight.value as u16;
+                    flight.resolved = true;
+                }
+            },
+        }
+        true
+    }
+
+    fn index(flight: &mut InFlight<M>, base: u16, index: u8, fixup: bool) {
+        let addr = base.wrapping_add(index as u16);
+        if fixup || page_cross(base, addr) {
+            flight.dummy_addr = Some((base & 0xFF00) | (addr & 0x00FF));
+        }
+        flight.base = base;
+        flight.addr = addr;
+        flight.resolved = true;
+    }
+
+    // The cycles after the address is known: the dummy read of an unfixed
+    // indexed address, then the access itself.
+    fn operand_cycle(&mut self, flight: &mut InFlight<M>, instruction: Instruction<M>) -> bool {
+        if let Some(addr) = flight.dummy_addr.take() {
+            self.mem_read(addr);
+            return false;
+        }
+        if flight.extra > 0 {
+            self.mem_read(self.program_counter);
+            flight.extra -= 1;
+            return flight.extra == 0;
+        }
+        flight.tail += 1;
+
+        let mode = instruction.opcode.add_mode;
+        match instruction.operation {
+            Operation::Read(handler) => {
+                let value = match mode {
+                    AddressingMode::NoneAddressing => {
+                        self.mem_read(self.program_counter);
+                        0
+                    }
+                    _ => self.mem_read(flight.addr),
+                };
+                let cycles = self.cycles;
+                handler(self, value);
+                flight.extra = self.cycles - cycles;
+                self.cycles = cycles;
+                flight.extra == 0
+            }
+            Operation::Write(handler) => {
+                let value = handler(self);
+                self.mem_write(flight.addr, value);
+                true
+            }
+            Operation::UnstableStore(handler) => {
+                let mut addr = flight.addr;
+                let value = handler(self) & ((flight.base >> 8) as u8).wrapping_add(1);
+                if page_cross(flight.base, addr) && self.unstable.sh_page_cross_corrupts_address {
+                    addr = (value as u16) << 8 | (addr & 0x00FF);
+                }
+                self.mem_write(addr, value);
+                true
+            }
+            Operation::Modify(handler) if mode == AddressingMode::Accumulator => {
+                self.mem_read(self.program_counter);
+                self.reg_a = handler(self, self.reg_a);
+                true
+            }
+            Operation::Modify(handler) => match flight.tail {
+                1 => {
+                    flight.value = self.mem_read(flight.addr);
+                    false
+                }
+                2 => {
+                    // NMOS writes the unmodified value back, the 65C02 reads it again
+                    if self.variant == CpuVariant::Cmos65C02 {
+                        self.mem_read(flight.addr);
+                    } else {
+                        self.mem_write(flight.addr, flight.value);
+                    }
+                    flight.value = handler(self, flight.value);
+                    false
+                }
+                _ => {
+                    self.mem_write(flight.addr, flight.value);
+                    true
+                }
+            },
+            _ => true,
+        }
+    }
+
+    fn stack_cycle(
+        &mut self,
+        flight: &mut InFlight<M>,
+        name: &str,
+        handler: fn(&mut CPU<M>),
+    ) -> bool {
+        let t = flight.cycle;
+        match name {
+            "BRK" => return self.interrupt_cycle(flight, BRK),
+            "PHA" | "PHP" | "PHX" | "PHY" => match t {
+                1 => {
+                    self.mem_read(self.program_counter);
+                }
+                _ => {
+                    handler(self);
+                    return true;
+                }
+            },
+            "PLA" | "PLP" | "PLX" | "PLY" => match t {
+                1 => {
+                    self.mem_read(self.program_counter);
+                }
+                2 => {
+                    self.mem_read(STACK_OFFSET + self.stp as u16);
+                }
+                _ => {
+                    handler(self);
+                    return true;
+                }
+            },
+            "RTS" => match t {
+                1 => {
+                    self.mem_read(self.program_counter);
+                }
+                2 => {
+                    self.mem_read(STACK_OFFSET + self.stp as u16);
+                }
+                3 => flight.value = self.pop(),
+                4 => {
+                    let hi = self.pop();
+                    self.program_counter = (hi as u16) << 8 | flight.value as u16;
+                }
+                _ => {
+                    self.mem_read(self.program_counter);
+                    self.program_counter = self.program_counter.wrapping_add(1);
+                    return true;
+                }
+            },
+            "RTI" => match t {
+                1 => {
+                    self.mem_read(self.program_counter);
+                }
+                2 => {
+                    self.mem_read(STACK_OFFSET + self.stp as u16);
+                }
+                3 => {
+                    let flags = self.pop();
+                    self.set_status_from_stack(flags);
+                }
+                4 => flight.value = self.pop(),
+                _ => {
+                    let hi = self.pop();
+                    self.program_counter = (hi as u16) << 8 | flight.value as u16;
+                    return true;
+                }
+            },
+            _ => {
+                handler(self);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn jsr_cycle(&mut self, flight: &mut InFlight<M>) -> bool {
+        match flight.cycle {
+            1 => flight.value = self.fetch_operand(),
+            2 => {
+                self.mem_read(STACK_OFFSET + self.stp as u16);
+            }
+            // PC is on the high address byte, which is what RTS expects back
+            3 => self.push((self.program_counter >> 8) as u8),
+            4 => self.push(self.program_counter as u8),
+            _ => {
+                let hi = self.mem_read(self.program_counter);
+                self.program_counter = (hi as u16) << 8 | flight.value as u16;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn branch_cycle(
+        &mut self,
+        flight: &mut InFlight<M>,
+        condition: fn(&CPU<M>) -> bool,
+    ) -> bool {
+        match flight.cycle {
+            1 => {
+                let offset = self.fetch_operand();
+                flight.addr = self.program_counter.wrapping_add(offset as i8 as u16);
+                !condition(self)
+            }
+            2 => {
+                self.mem_read(self.program_counter);
+                if page_cross(self.program_counter, flight.addr) {
+                    return false;
+                }
+                self.program_counter = flight.addr;
+                true
+            }
+            _ => {
+                self.mem_read((self.program_counter & 0xFF00) | (flight.addr & 0x00FF));
+                self.program_counter = flight.addr;
+                true
+            }
+        }
+    }
+}
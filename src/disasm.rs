@@ -0,0 +1,180 @@
+use crate::constants::AddressingMode;
+use crate::cpu::{CPU, CpuVariant};
+use crate::mem::Mem;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+// One decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisasmInstruction {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    // ".byte" for trailing bytes too short to hold the instruction they start
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    // assembler syntax: #$12, $12,X, ($34),Y, the target of a branch, ...
+    pub operand: String,
+    // only filled in when decoded against a CPU, see disassemble_at()
+    pub effective_addr: Option<u16>,
+}
+
+impl DisasmInstruction {
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.bytes.len() as u16)
+    }
+}
+
+// C000  4C F5 C5  JMP $C5F5
+impl fmt::Display for DisasmInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let line = format!(
+            "{:04X}  {:8} {:>5} {}",
+            self.addr,
+            hex.join(" "),
+            self.mnemonic,
+            self.operand
+        );
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+// Decodes the instruction at the start of `bytes`, which sits at `addr`.
+pub fn decode(variant: CpuVariant, addr: u16, bytes: &[u8]) -> DisasmInstruction {
+    let Some(&code) = bytes.first() else {
+        return data_byte(addr, 0);
+    };
+    let op = variant.opcode(code);
+    if bytes.len() < op.bytes as usize {
+        return data_byte(addr, code);
+    }
+
+    let arg8 = if op.bytes > 1 { bytes[1] } else { 0 };
+    let arg16 = if op.bytes > 2 {
+        (bytes[2] as u16) << 8 | arg8 as u16
+    } else {
+        arg8 as u16
+    };
+    let operand = match op.add_mode {
+        AddressingMode::NoneAddressing => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", arg8),
+        AddressingMode::Relative => format!("${:04X}", branch_target(addr, arg8)),
+        AddressingMode::ZeroPage => format!("${:02X}", arg8),
+        AddressingMode::ZeroPage_X => format!("${:02X},X", arg8),
+        AddressingMode::ZeroPage_Y => format!("${:02X},Y", arg8),
+        AddressingMode::Absolute => format!("${:04X}", arg16),
+        AddressingMode::Absolute_X => format!("${:04X},X", arg16),
+        AddressingMode::Absolute_Y => format!("${:04X},Y", arg16),
+        AddressingMode::Indirect => format!("(${:04X})", arg16),
+        AddressingMode::Indirect_X => format!("(${:02X},X)", arg8),
+        AddressingMode::Indirect_Y => format!("(${:02X}),Y", arg8),
+        AddressingMode::ZeroPage_Indirect => format!("(${:02X})", arg8),
+        AddressingMode::Absolute_X_Indirect => format!("(${:04X},X)", arg16),
+    };
+
+    DisasmInstruction {
+        addr,
+        bytes: bytes[..op.bytes as usize].to_vec(),
+        mnemonic: op.name,
+        mode: op.add_mode,
+        operand,
+        effective_addr: None,
+    }
+}
+
+fn data_byte(addr: u16, byte: u8) -> DisasmInstruction {
+    DisasmInstruction {
+        addr,
+        bytes: vec![byte],
+        mnemonic: ".byte",
+        mode: AddressingMode::NoneAddressing,
+        operand: format!("${:02X}", byte),
+        effective_addr: None,
+    }
+}
+
+fn branch_target(addr: u16, offset: u8) -> u16 {
+    addr.wrapping_add(2).wrapping_add(offset as i8 as u16)
+}
+
+// Disassembles a byte slice loaded at `origin`, front to back.
+pub fn disassemble(variant: CpuVariant, origin: u16, bytes: &[u8]) -> Vec<DisasmInstruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let instruction = decode(variant, origin.wrapping_add(offset as u16), &bytes[offset..]);
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+// Disassembles memory without side effects; the last instruction may run
+// past the end of the range.
+pub fn disassemble_range<M: Mem>(
+    variant: CpuVariant,
+    mem: &M,
+    range: RangeInclusive<u16>,
+) -> Vec<DisasmInstruction> {
+    let mut instructions = Vec::new();
+    let mut addr = *range.start() as u32;
+    while addr <= *range.end() as u32 {
+        let instruction = peek_instruction(variant, mem, addr as u16);
+        addr += instruction.bytes.len() as u32;
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+fn peek_instruction<M: Mem>(variant: CpuVariant, mem: &M, addr: u16) -> DisasmInstruction {
+    let bytes: Vec<u8> = (0..3).map(|i| mem.mem_peek(addr.wrapping_add(i))).collect();
+    decode(variant, addr, &bytes)
+}
+
+// Decodes the instruction at `addr` and works out the address it would
+// access with the CPU's current registers and memory.
+pub fn disassemble_at<M: Mem>(cpu: &CPU<M>, addr: u16) -> DisasmInstruction {
+    let mut instruction = peek_instruction(cpu.variant(), cpu, addr);
+    instruction.effective_addr = effective_address(cpu, &instruction);
+    instruction
+}
+
+fn effective_address<M: Mem>(cpu: &CPU<M>, instruction: &DisasmInstruction) -> Option<u16> {
+    let bytes = &instruction.bytes;
+    let arg8 = *bytes.get(1)?;
+    let arg16 = bytes.get(2).map_or(arg8 as u16, |&hi| (hi as u16) << 8 | arg8 as u16);
+    let zero_page_pointer = |ptr: u8| {
+        let lo = cpu.mem_peek(ptr as u16) as u16;
+        let hi = cpu.mem_peek(ptr.wrapping_add(1) as u16) as u16;
+        hi << 8 | lo
+    };
+
+    let addr = match instruction.mode {
+        AddressingMode::NoneAddressing
+        | AddressingMode::Accumulator
+        | AddressingMode::Immediate => return None,
+        AddressingMode::Relative => branch_target(instruction.addr, arg8),
+        AddressingMode::ZeroPage => arg8 as u16,
+        AddressingMode::ZeroPage_X => arg8.wrapping_add(cpu.reg_x) as u16,
+        AddressingMode::ZeroPage_Y => arg8.wrapping_add(cpu.reg_y) as u16,
+        AddressingMode::Absolute => arg16,
+        AddressingMode::Absolute_X => arg16.wrapping_add(cpu.reg_x as u16),
+        AddressingMode::Absolute_Y => arg16.wrapping_add(cpu.reg_y as u16),
+        AddressingMode::Indirect => {
+            let lo = cpu.mem_peek(arg16) as u16;
+            let hi = cpu.mem_peek(cpu.variant().indirect_jump_hi(arg16)) as u16;
+            hi << 8 | lo
+        }
+        AddressingMode::Indirect_X => zero_page_pointer(arg8.wrapping_add(cpu.reg_x)),
+        AddressingMode::Indirect_Y => zero_page_pointer(arg8).wrapping_add(cpu.reg_y as u16),
+        AddressingMode::ZeroPage_Indirect => zero_page_pointer(arg8),
+        AddressingMode::Absolute_X_Indirect => {
+            let ptr = arg16.wrapping_add(cpu.reg_x as u16);
+            let lo = cpu.mem_peek(ptr) as u16;
+            let hi = cpu.mem_peek(ptr.wrapping_add(1)) as u16;
+            hi << 8 | lo
+        }
+    };
+    Some(addr)
+}
@@ -0,0 +1,11 @@
+pub mod asm;
+pub mod bus;
+pub mod cartridge;
+pub mod constants;
+pub mod cpu;
+pub mod disasm;
+pub mod loader;
+pub mod mapper;
+pub mod mem;
+pub mod runner;
+pub mod trace;
@@ -0,0 +1,318 @@
+use crate::cpu::CPU;
+use crate::mem::Mem;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Binary,
+    IntelHex,
+    SRecord,
+}
+
+impl ImageFormat {
+    // Guesses from the file extension; anything unknown is a raw binary.
+    pub fn from_path(path: &Path) -> ImageFormat {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("hex" | "ihx" | "ihex") => ImageFormat::IntelHex,
+            Some("srec" | "s19" | "s28" | "s37" | "mot") => ImageFormat::SRecord,
+            _ => ImageFormat::Binary,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // a malformed HEX or S-record line
+    Parse { line: usize, message: String },
+    Checksum { line: usize },
+    // data starting at addr runs past $FFFF
+    DoesNotFit { addr: u32, len: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{e}"),
+            LoadError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            LoadError::Checksum { line } => write!(f, "line {line}: checksum mismatch"),
+            LoadError::DoesNotFit { addr, len } => write!(
+                f,
+                "{len} bytes at {addr:#06x} do not fit in the 64 KiB address space"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+}
+
+// The data in a program file, not yet written anywhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    // from a HEX start address record or an S7/S8/S9 record
+    pub entry: Option<u16>,
+}
+
+impl Image {
+    // Adds data at a 32-bit file address, joining it onto the previous
+    // segment when it continues it.
+    fn add(&mut self, addr: u32, bytes: &[u8]) -> Result<(), LoadError> {
+        if addr as usize + bytes.len() > 0x10000 {
+            return Err(LoadError::DoesNotFit {
+                addr,
+                len: bytes.len(),
+            });
+        }
+        match self.segments.last_mut() {
+            Some(last) if last.addr as usize + last.bytes.len() == addr as usize => {
+                last.bytes.extend_from_slice(bytes)
+            }
+            _ => self.segments.push(Segment {
+                addr: addr as u16,
+                bytes: bytes.to_vec(),
+            }),
+        }
+        Ok(())
+    }
+
+    pub fn lowest_addr(&self) -> Option<u16> {
+        self.segments.iter().map(|s| s.addr).min()
+    }
+
+    pub fn write_to<M: Mem>(&self, mem: &mut M) {
+        for segment in &self.segments {
+            for (i, byte) in segment.bytes.iter().enumerate() {
+                mem.mem_write(segment.addr.wrapping_add(i as u16), *byte);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    // None guesses from the file extension
+    pub format: Option<ImageFormat>,
+    // where a raw binary goes; HEX and S-record files carry their own addresses
+    pub addr: u16,
+    // overrides the entry point; otherwise the file's start record is used,
+    // then the lowest loaded address
+    pub entry: Option<u16>,
+    pub set_reset_vector: bool,
+    pub set_pc: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            format: None,
+            addr: 0x0600,
+            entry: None,
+            set_reset_vector: false,
+            set_pc: false,
+        }
+    }
+}
+
+pub fn parse_image(format: ImageFormat, data: &[u8], addr: u16) -> Result<Image, LoadError> {
+    match format {
+        ImageFormat::Binary => {
+            let mut image = Image::default();
+            image.add(addr as u32, data)?;
+            Ok(image)
+        }
+        ImageFormat::IntelHex => parse_intel_hex(&text(data)?),
+        ImageFormat::SRecord => parse_srecord(&text(data)?),
+    }
+}
+
+fn text(data: &[u8]) -> Result<String, LoadError> {
+    String::from_utf8(data.to_vec())
+        .map_err(|e| LoadError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+pub fn read_image(path: &Path, options: &LoadOptions) -> Result<Image, LoadError> {
+    let format = options
+        .format
+        .unwrap_or_else(|| ImageFormat::from_path(path));
+    parse_image(format, &std::fs::read(path)?, options.addr)
+}
+
+// Writes the image into the CPU's memory, then points the reset vector and/or
+// PC at the entry point as asked. Returns the entry point.
+pub fn load_image<M: Mem>(cpu: &mut CPU<M>, image: &Image, options: &LoadOptions) -> u16 {
+    image.write_to(cpu);
+    let entry = options
+        .entry
+        .or(image.entry)
+        .or(image.lowest_addr())
+        .unwrap_or(options.addr);
+    if options.set_reset_vector {
+        cpu.mem_write_u16(crate::constants::RESET_VECTOR, entry);
+    }
+    if options.set_pc {
+        cpu.program_counter = entry;
+    }
+    entry
+}
+
+pub fn load_file<M: Mem>(
+    cpu: &mut CPU<M>,
+    path: &Path,
+    options: &LoadOptions,
+) -> Result<u16, LoadError> {
+    let image = read_image(path, options)?;
+    Ok(load_image(cpu, &image, options))
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> LoadError {
+    LoadError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+fn hex_bytes(digits: &str, line: usize) -> Result<Vec<u8>, LoadError> {
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err(parse_error(line, "expected pairs of hex digits"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| parse_error(line, format!("bad hex digits {}", &digits[i..i + 2])))
+        })
+        .collect()
+}
+
+fn be_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |value, &b| value << 8 | b as u32)
+}
+
+// Start addresses past $FFFF cannot be a 6502 entry point.
+fn entry_point(value: u32, line: usize) -> Result<u16, LoadError> {
+    u16::try_from(value).map_err(|_| parse_error(line, format!("start address {value:#x}")))
+}
+
+// :LLAAAATT<data>CC, where the checksum makes all the bytes sum to zero
+fn parse_intel_hex(text: &str) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    let mut base = 0u32;
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let digits = line
+            .strip_prefix(':')
+            .ok_or_else(|| parse_error(number, "record does not start with ':'"))?;
+        let bytes = hex_bytes(digits, number)?;
+        if bytes.len() < 5 || bytes.len() != 5 + bytes[0] as usize {
+            return Err(parse_error(
+                number,
+                "record length does not match its count",
+            ));
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(LoadError::Checksum { line: number });
+        }
+        let addr = be_value(&bytes[1..3]);
+        let data = &bytes[4..bytes.len() - 1];
+        let data_len = match bytes[3] {
+            0x02 | 0x04 => Some(2),
+            0x03 | 0x05 => Some(4),
+            _ => None,
+        };
+        if let Some(len) = data_len
+            && data.len() != len
+        {
+            return Err(parse_error(
+                number,
+                format!("record type {:02X} needs {len} data bytes", bytes[3]),
+            ));
+        }
+        match bytes[3] {
+            0x00 => image.add(base + addr, data)?,
+            0x01 => break,
+            0x02 => base = be_value(data) << 4,
+            0x03 => {
+                let cs = be_value(&data[..2]);
+                let ip = be_value(&data[2..]);
+                image.entry = Some(entry_point((cs << 4) + ip, number)?);
+            }
+            0x04 => base = be_value(data) << 16,
+            0x05 => image.entry = Some(entry_point(be_value(data), number)?),
+            kind => {
+                return Err(parse_error(
+                    number,
+                    format!("unknown record type {kind:02X}"),
+                ));
+            }
+        }
+    }
+    Ok(image)
+}
+
+// S<type><count><address><data><checksum>, where the checksum is the ones'
+// complement of the sum of the count, address and data bytes
+fn parse_srecord(text: &str) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (kind, digits) = match line.as_bytes() {
+            [b'S' | b's', kind, ..] if kind.is_ascii_digit() => (kind - b'0', &line[2..]),
+            _ => return Err(parse_error(number, "record does not start with S0-S9")),
+        };
+        let bytes = hex_bytes(digits, number)?;
+        if bytes.is_empty() || bytes.len() != 1 + bytes[0] as usize {
+            return Err(parse_error(
+                number,
+                "record length does not match its count",
+            ));
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0xff {
+            return Err(LoadError::Checksum { line: number });
+        }
+        let addr_len = match kind {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(parse_error(number, format!("unknown record type S{kind}"))),
+        };
+        if bytes.len() < 2 + addr_len {
+            return Err(parse_error(number, "record too short for its address"));
+        }
+        let addr = be_value(&bytes[1..1 + addr_len]);
+        let data = &bytes[1 + addr_len..bytes.len() - 1];
+        match kind {
+            1..=3 => image.add(addr, data)?,
+            7..=9 => image.entry = Some(entry_point(addr, number)?),
+            // header and record counts
+            _ => {}
+        }
+    }
+    Ok(image)
+}
@@ -1,7 +1,11 @@
-mod constants;
-mod cpu;
-mod bus;
-use crate::cpu::CPU;
+use nes_emulator::asm;
+use nes_emulator::bus::Bus;
+use nes_emulator::cartridge::{PRG_ROM_PAGE_SIZE, Region, Rom};
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::disasm;
+use nes_emulator::loader::{self, ImageFormat, LoadOptions};
+use nes_emulator::mem::{FlatMemory, Mem};
+use nes_emulator::runner::{self, StopCondition, WriteWatch};
 
 use rand::Rng;
 use sdl2::EventPump;
@@ -47,7 +51,7 @@ fn handle_user_input(cpu: &mut CPU, event_pump: &mut EventPump) {
     }
 }
 
-fn read_screen_state(cpu: &CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
+fn read_screen_state(cpu: &mut CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
     let mut frame_idx = 0;
     let mut update = false;
     for i in 0x0200..0x600 {
@@ -78,7 +82,224 @@ fn color(byte: u8) -> Color {
     }
 }
 
+fn parse_addr(arg: &str) -> Result<u16, String> {
+    let digits = arg.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("bad address: {}", arg))
+}
+
+// disasm <rom.nes> [--bank N] [--org ADDR]
+//
+// Without --bank every PRG bank is printed. Banks default to $8000, except
+// the last one (or the only one) which sits at $C000 where the vectors are.
+fn run_disasm(args: &[String]) -> Result<(), String> {
+    let usage = "usage: disasm <rom.nes> [--bank N] [--org ADDR]";
+    let mut path = None;
+    let mut bank = None;
+    let mut org = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bank" => {
+                let n = args.next().ok_or(usage)?;
+                bank = Some(n.parse::<usize>().map_err(|_| format!("bad bank: {}", n))?);
+            }
+            "--org" => org = Some(parse_addr(args.next().ok_or(usage)?)?),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(usage.to_string()),
+        }
+    }
+    let path = path.ok_or(usage)?;
+    let rom = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let rom = Rom::new(&rom).map_err(|e| format!("{}: {}", path, e))?;
+    let banks: Vec<&[u8]> = rom.prg_rom.chunks(PRG_ROM_PAGE_SIZE).collect();
+    if let Some(b) = bank.filter(|&b| b >= banks.len()) {
+        return Err(format!("no PRG bank {} (ROM has {})", b, banks.len()));
+    }
+
+    for (i, prg) in banks.iter().enumerate() {
+        if bank.is_some_and(|b| b != i) {
+            continue;
+        }
+        let origin = org.unwrap_or(if i + 1 == banks.len() { 0xc000 } else { 0x8000 });
+        println!("; PRG bank {}", i);
+        for instruction in disasm::disassemble(CpuVariant::Ricoh2A03, origin, prg) {
+            println!("{}", instruction);
+        }
+    }
+    Ok(())
+}
+
+const RUN_USAGE: &str = "usage: run <program|rom.nes> [--format bin|hex|srec] [--addr ADDR] \
+[--entry ADDR] [--cpu 2a03|6502|65c02] [--until-pc ADDR] [--pass ADDR] \
+[--max-instructions N] [--max-cycles N] [--brk] [--self-loop] [--until-write ADDR] \
+[--dump START-END]";
+
+fn parse_count(arg: Option<&String>) -> Result<u64, String> {
+    let arg = arg.ok_or(RUN_USAGE)?;
+    arg.parse().map_err(|_| format!("bad count: {}", arg))
+}
+
+// run <program> [options]
+//
+// Runs a bare 6502 program in 64 KiB of RAM with no window. It stops on the
+// first condition given, or on BRK or a self-loop when none are. A program
+// without its own reset vector or start record starts at its first byte.
+// An iNES file runs as a cartridge on the NES bus instead, from its reset
+// vector, and its run time is reported at the header's region clock.
+//
+// Exits 0 on a clean stop and 1 when the CPU faults, a --max limit is hit, or
+// --pass was given and the program stopped anywhere else. Usage and load
+// errors exit 2.
+fn run_program(args: &[String]) -> Result<bool, String> {
+    let mut path = None;
+    let mut options = LoadOptions::default();
+    let mut variant = None;
+    let mut conditions = Vec::new();
+    let mut pass = None;
+    let mut dumps = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut addr = || parse_addr(args.next().ok_or(RUN_USAGE)?);
+        match arg.as_str() {
+            "--format" => {
+                options.format = Some(match args.next().map(String::as_str) {
+                    Some("bin") => ImageFormat::Binary,
+                    Some("hex") => ImageFormat::IntelHex,
+                    Some("srec") => ImageFormat::SRecord,
+                    _ => return Err(RUN_USAGE.to_string()),
+                })
+            }
+            "--addr" => options.addr = addr()?,
+            "--entry" => options.entry = Some(addr()?),
+            "--cpu" => {
+                variant = Some(match args.next().map(String::as_str) {
+                    Some("2a03") => CpuVariant::Ricoh2A03,
+                    Some("6502") => CpuVariant::Nmos6502,
+                    Some("65c02") => CpuVariant::Cmos65C02,
+                    _ => return Err(RUN_USAGE.to_string()),
+                })
+            }
+            "--until-pc" => conditions.push(StopCondition::Pc(addr()?)),
+            "--pass" => {
+                let target = addr()?;
+                pass = Some(target);
+                conditions.push(StopCondition::Pc(target));
+            }
+            "--until-write" => conditions.push(StopCondition::Write(addr()?)),
+            "--max-instructions" => {
+                conditions.push(StopCondition::Instructions(parse_count(args.next())?))
+            }
+            "--max-cycles" => conditions.push(StopCondition::Cycles(parse_count(args.next())?)),
+            "--brk" => conditions.push(StopCondition::Brk),
+            "--self-loop" => conditions.push(StopCondition::SelfLoop),
+            "--dump" => {
+                let range = args.next().ok_or(RUN_USAGE)?;
+                let (start, end) = range
+                    .split_once('-')
+                    .ok_or_else(|| format!("bad range: {}", range))?;
+                dumps.push((parse_addr(start)?, parse_addr(end)?));
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(RUN_USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(RUN_USAGE)?;
+    if conditions.is_empty() {
+        conditions = vec![StopCondition::Brk, StopCondition::SelfLoop];
+    }
+
+    let raw = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if raw.starts_with(b"NES\x1A") {
+        let rom = Rom::new(&raw).map_err(|e| format!("{}: {}", path, e))?;
+        let bus = Bus::with_rom(rom).map_err(|e| format!("{}: {}", path, e))?;
+        let variant = variant.unwrap_or(CpuVariant::Ricoh2A03);
+        let mut cpu = CPU::with_variant(WriteWatch::new(bus), variant);
+        cpu.power_on();
+        if let Some(entry) = options.entry {
+            cpu.program_counter = entry;
+        }
+        let region = cpu.bus.inner.region;
+        return Ok(report(&mut cpu, region, &conditions, pass, &dumps));
+    }
+
+    let image = loader::read_image(path.as_ref(), &options)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let variant = variant.unwrap_or(CpuVariant::Nmos6502);
+    let mut cpu = CPU::with_variant(WriteWatch::new(FlatMemory::new()), variant);
+    // a program that brings its own reset vector starts there
+    let has_vector = image.segments.iter().any(|s| {
+        let end = s.addr as u32 + s.bytes.len() as u32;
+        s.addr <= 0xfffc && end >= 0xfffe
+    });
+    let explicit_entry = options.entry.or(image.entry);
+    options.set_reset_vector = !has_vector;
+    let entry = loader::load_image(&mut cpu, &image, &options);
+    cpu.power_on();
+    if explicit_entry.is_some() || !has_vector {
+        cpu.program_counter = entry;
+    }
+    // bare programs have no header to say otherwise
+    Ok(report(&mut cpu, Region::Ntsc, &conditions, pass, &dumps))
+}
+
+// Runs to a stop condition and prints where, how long that took on the
+// region's CPU clock, the registers and any dumps. True when the run passed.
+fn report<M: Mem>(
+    cpu: &mut CPU<WriteWatch<M>>,
+    region: Region,
+    conditions: &[StopCondition],
+    pass: Option<u16>,
+    dumps: &[(u16, u16)],
+) -> bool {
+    let result = runner::run(cpu, conditions);
+    let passed = match &result {
+        Ok(stopped) => {
+            let ms = stopped.cycles as f64 * 1000.0 / region.cpu_clock_hz() as f64;
+            println!(
+                "stopped: {} after {} instructions, {} cycles ({:.3} ms at {:?})",
+                stopped.condition, stopped.instructions, stopped.cycles, ms, region
+            );
+            match stopped.condition {
+                StopCondition::Instructions(_) | StopCondition::Cycles(_) => false,
+                condition => pass.is_none_or(|target| condition == StopCondition::Pc(target)),
+            }
+        }
+        Err(e) => {
+            println!("error: {}", e);
+            false
+        }
+    };
+    println!("{}", runner::registers(cpu));
+    for &(start, end) in dumps {
+        print!("{}", runner::hex_dump(cpu, start, end));
+    }
+    passed
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("disasm") => {
+            if let Err(e) = run_disasm(&args[1..]) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("run") => match run_program(&args[1..]) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        _ => {}
+    }
+    run_snake();
+}
+
+fn run_snake() {
     // init sdl2
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -97,40 +318,31 @@ fn main() {
         .create_texture_target(PixelFormatEnum::RGB24, 32, 32)
         .unwrap();
 
-    let game_code = vec![
-        0x20, 0x06, 0x06, 0x20, 0x38, 0x06, 0x20, 0x0d, 0x06, 0x20, 0x2a, 0x06, 0x60, 0xa9, 0x02,
-        0x85, 0x02, 0xa9, 0x04, 0x85, 0x03, 0xa9, 0x11, 0x85, 0x10, 0xa9, 0x10, 0x85, 0x12, 0xa9,
-        0x0f, 0x85, 0x14, 0xa9, 0x04, 0x85, 0x11, 0x85, 0x13, 0x85, 0x15, 0x60, 0xa5, 0xfe, 0x85,
-        0x00, 0xa5, 0xfe, 0x29, 0x03, 0x18, 0x69, 0x02, 0x85, 0x01, 0x60, 0x20, 0x4d, 0x06, 0x20,
-        0x8d, 0x06, 0x20, 0xc3, 0x06, 0x20, 0x19, 0x07, 0x20, 0x20, 0x07, 0x20, 0x2d, 0x07, 0x4c,
-        0x38, 0x06, 0xa5, 0xff, 0xc9, 0x77, 0xf0, 0x0d, 0xc9, 0x64, 0xf0, 0x14, 0xc9, 0x73, 0xf0,
-        0x1b, 0xc9, 0x61, 0xf0, 0x22, 0x60, 0xa9, 0x04, 0x24, 0x02, 0xd0, 0x26, 0xa9, 0x01, 0x85,
-        0x02, 0x60, 0xa9, 0x08, 0x24, 0x02, 0xd0, 0x1b, 0xa9, 0x02, 0x85, 0x02, 0x60, 0xa9, 0x01,
-        0x24, 0x02, 0xd0, 0x10, 0xa9, 0x04, 0x85, 0x02, 0x60, 0xa9, 0x02, 0x24, 0x02, 0xd0, 0x05,
-        0xa9, 0x08, 0x85, 0x02, 0x60, 0x60, 0x20, 0x94, 0x06, 0x20, 0xa8, 0x06, 0x60, 0xa5, 0x00,
-        0xc5, 0x10, 0xd0, 0x0d, 0xa5, 0x01, 0xc5, 0x11, 0xd0, 0x07, 0xe6, 0x03, 0xe6, 0x03, 0x20,
-        0x2a, 0x06, 0x60, 0xa2, 0x02, 0xb5, 0x10, 0xc5, 0x10, 0xd0, 0x06, 0xb5, 0x11, 0xc5, 0x11,
-        0xf0, 0x09, 0xe8, 0xe8, 0xe4, 0x03, 0xf0, 0x06, 0x4c, 0xaa, 0x06, 0x4c, 0x35, 0x07, 0x60,
-        0xa6, 0x03, 0xca, 0x8a, 0xb5, 0x10, 0x95, 0x12, 0xca, 0x10, 0xf9, 0xa5, 0x02, 0x4a, 0xb0,
-        0x09, 0x4a, 0xb0, 0x19, 0x4a, 0xb0, 0x1f, 0x4a, 0xb0, 0x2f, 0xa5, 0x10, 0x38, 0xe9, 0x20,
-        0x85, 0x10, 0x90, 0x01, 0x60, 0xc6, 0x11, 0xa9, 0x01, 0xc5, 0x11, 0xf0, 0x28, 0x60, 0xe6,
-        0x10, 0xa9, 0x1f, 0x24, 0x10, 0xf0, 0x1f, 0x60, 0xa5, 0x10, 0x18, 0x69, 0x20, 0x85, 0x10,
-        0xb0, 0x01, 0x60, 0xe6, 0x11, 0xa9, 0x06, 0xc5, 0x11, 0xf0, 0x0c, 0x60, 0xc6, 0x10, 0xa5,
-        0x10, 0x29, 0x1f, 0xc9, 0x1f, 0xf0, 0x01, 0x60, 0x4c, 0x35, 0x07, 0xa0, 0x00, 0xa5, 0xfe,
-        0x91, 0x00, 0x60, 0xa6, 0x03, 0xa9, 0x00, 0x81, 0x10, 0xa2, 0x00, 0xa9, 0x01, 0x81, 0x10,
-        0x60, 0xa6, 0xff, 0xea, 0xea, 0xca, 0xd0, 0xfb, 0x60,
-    ];
+    let game_code = match asm::assemble(include_str!("../programs/snake.asm")) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("snake.asm: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     //load the game
-    let mut cpu = CPU::new();
+    let bus = Bus::new();
+    let mut cpu = CPU::new(bus);
+    cpu.power_on();
     cpu.load(game_code);
-    cpu.reset();
 
     // run the game cycle
-    let mut screen_state = [0 as u8; 32 * 3 * 32];
+    let mut screen_state = [0_u8; 32 * 3 * 32];
     let mut rng = rand::thread_rng();
 
-    cpu.run_with_callback(move |cpu| {
+    let result = cpu.run_with_callback(move |cpu| {
+        // gameOver falls through to BRK; quit there rather than take the
+        // interrupt through the empty vector at $FFFE
+        if cpu.mem_read(cpu.program_counter) == 0x00 {
+            std::process::exit(0);
+        }
+
         handle_user_input(cpu, &mut event_pump);
         cpu.mem_write(0xfe, rng.gen_range(1, 16));
 
@@ -142,4 +354,9 @@ fn main() {
 
         ::std::thread::sleep(std::time::Duration::new(0, 70_000));
     });
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
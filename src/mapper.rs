@@ -0,0 +1,132 @@
+use crate::cartridge::{Mirroring, Rom, RomError};
+
+mod discrete;
+mod mmc1;
+mod nrom;
+
+pub use discrete::{Board, Discrete};
+pub use mmc1::Mmc1;
+pub use nrom::Nrom;
+
+const PRG_RAM_SIZE: usize = 0x2000;
+const TRAINER_OFFSET: usize = 0x1000;
+
+// The cartridge side of the bus. The CPU sees it at $4020-$FFFF and the PPU
+// at $0000-$1FFF (pattern tables); nametables stay in the console's VRAM,
+// arranged as mirroring() says.
+pub trait Mapper {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.cpu_peek(addr)
+    }
+
+    // read without side effects, for tracing and debugging
+    fn cpu_peek(&self, addr: u16) -> u8;
+
+    fn cpu_write(&mut self, addr: u16, data: u8);
+
+    fn ppu_read(&mut self, addr: u16) -> u8;
+
+    fn ppu_write(&mut self, addr: u16, data: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    // power cycle and reset button; neither touches what a battery keeps
+    fn power_on(&mut self) {}
+
+    fn reset(&mut self) {}
+
+    // whether the cartridge is pulling the CPU's IRQ line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // called once per CPU cycle, for mappers with cycle counters
+    fn cpu_cycle(&mut self) {}
+
+    // called at the end of every visible scanline, for scanline counters
+    fn scanline(&mut self) {}
+
+    // the part of PRG RAM kept alive by a battery, for saving and restoring
+    // games; None when the cartridge has no battery-backed PRG RAM
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+}
+
+// Picks the mapper implementation for the header's mapper number.
+pub fn for_rom(rom: Rom) -> Result<Box<dyn Mapper>, RomError> {
+    match rom.mapper {
+        0 => Ok(Box::new(Nrom::new(rom))),
+        1 => Ok(Box::new(Mmc1::new(rom))),
+        // the latch boards, or UnsupportedMapper
+        _ => Ok(Box::new(Discrete::new(rom)?)),
+    }
+}
+
+// PRG RAM for $6000-$7FFF as the header sizes it: the volatile part first,
+// then the battery-backed part. A trainer sits at $7000 and forces the full
+// 8 KiB. Smaller RAM mirrors through the window; none at all reads as 0.
+struct PrgRam {
+    data: Vec<u8>,
+    nvram_size: usize,
+    trainer: Option<Vec<u8>>,
+}
+
+impl PrgRam {
+    fn new(rom: &Rom) -> Self {
+        let mut size = rom.prg_ram_size + rom.prg_nvram_size;
+        if rom.trainer.is_some() {
+            size = size.max(PRG_RAM_SIZE);
+        }
+        let mut ram = PrgRam {
+            data: vec![0; size],
+            nvram_size: rom.prg_nvram_size,
+            trainer: rom.trainer.clone(),
+        };
+        ram.power_on();
+        ram
+    }
+
+    // the volatile part comes up cleared and the trainer is loaded again;
+    // what the battery keeps is left alone
+    fn power_on(&mut self) {
+        let volatile = self.data.len() - self.nvram_size;
+        self.data[..volatile].fill(0);
+        if let Some(trainer) = &self.trainer {
+            self.data[TRAINER_OFFSET..TRAINER_OFFSET + trainer.len()].copy_from_slice(trainer);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn battery(&self) -> Option<&[u8]> {
+        let volatile = self.data.len() - self.nvram_size;
+        (self.nvram_size > 0).then_some(&self.data[volatile..])
+    }
+
+    fn battery_mut(&mut self) -> Option<&mut [u8]> {
+        let volatile = self.data.len() - self.nvram_size;
+        (self.nvram_size > 0).then_some(&mut self.data[volatile..])
+    }
+
+    fn read(&self, offset: usize) -> u8 {
+        if self.data.is_empty() {
+            0
+        } else {
+            self.data[offset % self.data.len()]
+        }
+    }
+
+    fn write(&mut self, offset: usize, data: u8) {
+        if !self.data.is_empty() {
+            let len = self.data.len();
+            self.data[offset % len] = data;
+        }
+    }
+}
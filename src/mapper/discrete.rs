@@ -0,0 +1,247 @@
+use super::{Mapper, PrgRam};
+use crate::cartridge::{Mirroring, Rom, RomError};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+// Boards built from a latch and a few logic chips instead of a mapper ASIC.
+// A write anywhere in $8000-$FFFF loads the latch, whose bits pick the banks:
+//
+//   UxROM (2)         16 KiB PRG at $8000, last bank fixed at $C000
+//   CNROM (3)         8 KiB CHR
+//   AxROM (7)         32 KiB PRG in bits 0-2, single-screen page in bit 4
+//   Color Dreams (11) 32 KiB PRG in bits 0-1, 8 KiB CHR in bits 4-7
+//   BNROM (34)        32 KiB PRG
+//   GxROM (66)        32 KiB PRG in bits 4-5, 8 KiB CHR in bits 0-1
+//
+// Mapper 34 is also the NINA-001, which has its registers at $7FFD-$7FFF
+// instead, on top of 8 KiB of PRG RAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Board {
+    UxRom,
+    CnRom,
+    AxRom,
+    ColorDreams,
+    BnRom,
+    Nina001,
+    GxRom,
+}
+
+// Per-board quirks. With bus conflicts the latch and the ROM both drive the
+// data bus during the write, so the latch gets the value ANDed with the ROM
+// byte at that address; games work around it by writing to a byte that
+// already holds the value.
+struct Quirks {
+    mapper: u16,
+    board: Board,
+    bus_conflicts: bool,
+}
+
+const BOARDS: [Quirks; 6] = [
+    Quirks {
+        mapper: 2,
+        board: Board::UxRom,
+        bus_conflicts: true,
+    },
+    Quirks {
+        mapper: 3,
+        board: Board::CnRom,
+        bus_conflicts: true,
+    },
+    // ANROM has none; some AOROM boards do and say so with submapper 2
+    Quirks {
+        mapper: 7,
+        board: Board::AxRom,
+        bus_conflicts: false,
+    },
+    Quirks {
+        mapper: 11,
+        board: Board::ColorDreams,
+        bus_conflicts: false,
+    },
+    Quirks {
+        mapper: 34,
+        board: Board::BnRom,
+        bus_conflicts: true,
+    },
+    Quirks {
+        mapper: 66,
+        board: Board::GxRom,
+        bus_conflicts: true,
+    },
+];
+
+// Looks the board up in the table; NES 2.0 submappers settle what the table
+// can only guess.
+fn board(rom: &Rom) -> Option<(Board, bool)> {
+    let quirks = BOARDS.iter().find(|quirks| quirks.mapper == rom.mapper)?;
+    Some(match (quirks.board, rom.submapper) {
+        (Board::UxRom | Board::CnRom | Board::AxRom, 1) => (quirks.board, false),
+        (Board::UxRom | Board::CnRom | Board::AxRom, 2) => (quirks.board, true),
+        (Board::BnRom, 1) => (Board::Nina001, false),
+        // iNES 1.0 files tell the NINA-001 apart by its CHR ROM
+        (Board::BnRom, 0) if rom.chr_rom.len() > 0x2000 => (Board::Nina001, false),
+        (board, _) => (board, quirks.bus_conflicts),
+    })
+}
+
+pub struct Discrete {
+    board: Board,
+    bus_conflicts: bool,
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    header_mirroring: Mirroring,
+    mirroring: Mirroring,
+    // 16 KiB banks at $8000 and $C000, 4 KiB banks at PPU $0000 and $1000
+    prg_banks: [usize; 2],
+    chr_banks: [usize; 2],
+}
+
+impl Discrete {
+    pub fn new(rom: Rom) -> Result<Self, RomError> {
+        let (board, bus_conflicts) = board(&rom).ok_or(RomError::UnsupportedMapper(rom.mapper))?;
+        let mut discrete = Discrete {
+            board,
+            bus_conflicts,
+            prg_ram: PrgRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: rom.chr_rom,
+            chr_is_ram: rom.chr_is_ram,
+            header_mirroring: rom.screen_mirroring,
+            mirroring: rom.screen_mirroring,
+            prg_banks: [0, 1],
+            chr_banks: [0, 1],
+        };
+        discrete.power_on();
+        Ok(discrete)
+    }
+
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    fn select_prg_32k(&mut self, bank: u8) {
+        self.prg_banks = [bank as usize * 2, bank as usize * 2 + 1];
+    }
+
+    fn select_chr_8k(&mut self, bank: u8) {
+        self.chr_banks = [bank as usize * 2, bank as usize * 2 + 1];
+    }
+
+    fn write_latch(&mut self, data: u8) {
+        match self.board {
+            Board::UxRom => self.prg_banks[0] = data as usize,
+            Board::CnRom => self.select_chr_8k(data),
+            Board::AxRom => {
+                self.select_prg_32k(data & 0b111);
+                self.mirroring = if data & 0b1_0000 != 0 {
+                    Mirroring::SingleScreenUpper
+                } else {
+                    Mirroring::SingleScreenLower
+                };
+            }
+            Board::ColorDreams => {
+                self.select_prg_32k(data & 0b11);
+                self.select_chr_8k(data >> 4);
+            }
+            Board::BnRom => self.select_prg_32k(data),
+            Board::GxRom => {
+                self.select_prg_32k((data >> 4) & 0b11);
+                self.select_chr_8k(data & 0b11);
+            }
+            // no latch at $8000
+            Board::Nina001 => {}
+        }
+    }
+
+    fn write_nina001(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x7FFD => self.select_prg_32k(data & 1),
+            0x7FFE => self.chr_banks[0] = (data & 0x0F) as usize,
+            0x7FFF => self.chr_banks[1] = (data & 0x0F) as usize,
+            _ => {}
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr >> 12) as usize & 1];
+        (bank * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+}
+
+impl Mapper for Discrete {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read((addr - 0x6000) as usize),
+            0x8000..=0xFFFF => {
+                let bank = self.prg_banks[(addr >= 0xC000) as usize];
+                let offset = bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+                self.prg_rom[offset % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                // the NINA-001's registers sit on top of its RAM
+                self.prg_ram.write((addr - 0x6000) as usize, data);
+                if self.board == Board::Nina001 {
+                    self.write_nina001(addr, data);
+                }
+            }
+            0x8000..=0xFFFF => {
+                let data = if self.bus_conflicts {
+                    data & self.cpu_peek(addr)
+                } else {
+                    data
+                };
+                self.write_latch(data);
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    // the latch comes up empty; the reset button leaves it alone
+    fn power_on(&mut self) {
+        // NES 2.0 allows less than one bank; it mirrors through the window
+        let last = (self.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(1);
+        self.prg_banks = if self.board == Board::UxRom {
+            [0, last]
+        } else {
+            [0, 1]
+        };
+        self.chr_banks = [0, 1];
+        self.mirroring = match self.board {
+            Board::AxRom => Mirroring::SingleScreenLower,
+            _ => self.header_mirroring,
+        };
+        self.prg_ram.power_on();
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_mut()
+    }
+}
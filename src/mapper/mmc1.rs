@@ -0,0 +1,250 @@
+use super::{Mapper, PrgRam};
+use crate::cartridge::{Mirroring, Rom};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+const PRG_RAM_BANK_SIZE: usize = 0x2000;
+// larger boards split PRG ROM into 256 KiB halves
+const PRG_OUTER_SIZE: usize = 0x40000;
+const CHR_8K: usize = 0x2000;
+
+// the shift register is empty when only this marker bit is left
+const SHIFT_EMPTY: u8 = 0b1_0000;
+
+// Mapper 1: MMC1, on the SxROM boards. The CPU loads its four registers one
+// bit at a time through a shift register at $8000-$FFFF:
+//
+//   $8000 control: mirroring (bits 0-1), PRG mode (2-3), 4 KiB CHR (4)
+//   $A000 CHR bank 0
+//   $C000 CHR bank 1
+//   $E000 PRG bank (bits 0-3), PRG RAM disable (bit 4)
+//
+// Boards with only 8 KiB of CHR use the spare CHR bank bits elsewhere:
+// SUROM and SXROM pick the 256 KiB half of a 512 KiB PRG ROM with bit 4,
+// SOROM picks one of two 8 KiB PRG RAM banks with bit 3 and SXROM one of
+// four with bits 3 and 2, and SNROM disables its PRG RAM with bit 4.
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    shift: u8,
+    control: u8,
+    chr_bank: [u8; 2],
+    prg_bank: u8,
+    // which CHR bank register the board follows for the SxROM extras: the
+    // one for the pattern table the PPU touched last
+    chr_a12: bool,
+    // CPU cycles since power-on and the one the last serial write landed on
+    cycle: u64,
+    last_write: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(rom: Rom) -> Self {
+        let mut mmc1 = Mmc1 {
+            prg_ram: PrgRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: rom.chr_rom,
+            chr_is_ram: rom.chr_is_ram,
+            shift: SHIFT_EMPTY,
+            control: 0,
+            chr_bank: [0; 2],
+            prg_bank: 0,
+            chr_a12: false,
+            cycle: 0,
+            last_write: None,
+        };
+        mmc1.power_on();
+        mmc1
+    }
+
+    // The CPU writes one bit at a time, low bit first; the fifth write
+    // commits the value to the register picked by address bits 13-14. Bit 7
+    // set empties the shift register and restores the fixed-last PRG mode.
+    fn write_serial(&mut self, addr: u16, data: u8) {
+        // the second write of a read-modify-write instruction, one cycle
+        // after the first, does not register
+        let consecutive = self.last_write.is_some_and(|last| self.cycle - last <= 1);
+        self.last_write = Some(self.cycle);
+        if consecutive {
+            return;
+        }
+
+        if data & 0x80 != 0 {
+            self.reset_shift();
+            return;
+        }
+        let full = self.shift & 1 != 0;
+        self.shift = (self.shift >> 1) | ((data & 1) << 4);
+        if full {
+            let value = self.shift;
+            match addr {
+                0x8000..=0x9FFF => self.control = value,
+                0xA000..=0xBFFF => self.chr_bank[0] = value,
+                0xC000..=0xDFFF => self.chr_bank[1] = value,
+                _ => self.prg_bank = value,
+            }
+            self.shift = SHIFT_EMPTY;
+        }
+    }
+
+    fn reset_shift(&mut self) {
+        self.shift = SHIFT_EMPTY;
+        self.control |= 0b0_1100;
+    }
+
+    fn chr_4k(&self) -> bool {
+        self.control & 0b1_0000 != 0
+    }
+
+    // the CHR bank register whose spare bits drive the SxROM extras
+    fn chr_select(&self) -> u8 {
+        if self.chr_4k() && self.chr_a12 {
+            self.chr_bank[1]
+        } else {
+            self.chr_bank[0]
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        // bit 4 counts in 16 KiB banks, so it lands on the second half
+        let outer = if self.prg_rom.len() > PRG_OUTER_SIZE {
+            self.chr_select() as usize & 0b1_0000
+        } else {
+            0
+        };
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let upper = addr >= 0xC000;
+        let bank = match (self.control >> 2) & 0b11 {
+            // 32 KiB at $8000, ignoring the low bit of the bank number
+            0 | 1 => (bank & !1) | upper as usize,
+            // first bank fixed at $8000
+            2 => {
+                if upper {
+                    bank
+                } else {
+                    0
+                }
+            }
+            // last bank fixed at $C000
+            _ => {
+                if upper {
+                    0x0F
+                } else {
+                    bank
+                }
+            }
+        };
+        ((outer | bank) * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1)))
+            % self.prg_rom.len()
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        // SNROM: 8 KiB of CHR and PRG RAM, no outer PRG bank
+        let snrom = self.chr.len() == CHR_8K
+            && self.prg_rom.len() <= PRG_OUTER_SIZE
+            && self.prg_ram.len() == PRG_RAM_BANK_SIZE;
+        self.prg_bank & 0b1_0000 == 0 && !(snrom && self.chr_select() & 0b1_0000 != 0)
+    }
+
+    fn prg_ram_offset(&self, addr: u16) -> usize {
+        let bank = match self.prg_ram.len() / PRG_RAM_BANK_SIZE {
+            // SOROM
+            2 => (self.chr_select() >> 3) & 1,
+            // SXROM
+            4 => {
+                let chr = self.chr_select();
+                ((chr >> 3) & 1) | ((chr >> 1) & 0b10)
+            }
+            _ => 0,
+        };
+        bank as usize * PRG_RAM_BANK_SIZE + (addr - 0x6000) as usize
+    }
+
+    fn chr_offset(&mut self, addr: u16) -> usize {
+        self.chr_a12 = addr & 0x1000 != 0;
+        let bank = if self.chr_4k() {
+            self.chr_bank[self.chr_a12 as usize]
+        } else {
+            // 8 KiB at a time, ignoring the low bit of the bank number
+            (self.chr_bank[0] & !1) | self.chr_a12 as u8
+        };
+        (bank as usize * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                self.prg_ram.read(self.prg_ram_offset(addr))
+            }
+            0x8000..=0xFFFF => self.prg_rom[self.prg_offset(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                let offset = self.prg_ram_offset(addr);
+                self.prg_ram.write(offset, data);
+            }
+            0x8000..=0xFFFF => self.write_serial(addr, data),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let offset = self.chr_offset(addr);
+        self.chr[offset]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        let offset = self.chr_offset(addr);
+        if self.chr_is_ram {
+            self.chr[offset] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    // powers up with the last PRG bank fixed at $C000
+    fn power_on(&mut self) {
+        self.shift = SHIFT_EMPTY;
+        self.control = 0b0_1100;
+        self.chr_bank = [0; 2];
+        self.prg_bank = 0;
+        self.chr_a12 = false;
+        self.last_write = None;
+        self.prg_ram.power_on();
+    }
+
+    // the MMC1 has no reset line of its own, so the board comes out of reset
+    // as a bit 7 write leaves it: shift register empty, last PRG bank fixed
+    // at $C000 for the vector fetch. The next serial write always registers.
+    fn reset(&mut self) {
+        self.reset_shift();
+        self.last_write = None;
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_mut()
+    }
+}
@@ -0,0 +1,67 @@
+pub trait Mem {
+    fn mem_read(&mut self, addr: u16) -> u8;
+
+    // read without side effects, for tracing and debugging
+    fn mem_peek(&self, addr: u16) -> u8;
+
+    fn mem_write(&mut self, addr: u16, data: u8);
+
+    // power cycle and reset button, for whatever is attached to the bus
+    fn power_on(&mut self) {}
+
+    fn reset(&mut self) {}
+
+    // the CPU finished this many cycles, for hardware clocked alongside it
+    fn clock(&mut self, _cycles: u64) {}
+
+    // whether something on the bus, a cartridge mapper say, holds IRQ low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    fn mem_read_u16(&mut self, pos: u16) -> u16 {
+        let lo = self.mem_read(pos) as u16;
+        let hi = self.mem_read(pos.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    fn mem_write_u16(&mut self, pos: u16, data: u16) {
+        let hi = (data >> 8) as u8;
+        let lo = (data & 0xff) as u8;
+        self.mem_write(pos, lo);
+        self.mem_write(pos.wrapping_add(1), hi);
+    }
+}
+
+// plain 64 KiB of RAM for running bare 6502 programs
+pub struct FlatMemory {
+    memory: Box<[u8; 0x10000]>,
+}
+
+impl FlatMemory {
+    pub fn new() -> Self {
+        FlatMemory {
+            memory: Box::new([0; 0x10000]),
+        }
+    }
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mem for FlatMemory {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.memory[addr as usize] = data;
+    }
+}
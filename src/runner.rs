@@ -0,0 +1,185 @@
+use crate::constants::CpuError;
+use crate::cpu::{CPU, RunControl, StepInfo};
+use crate::mem::Mem;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCondition {
+    // PC reached the address; the instruction there has not run yet
+    Pc(u16),
+    Instructions(u64),
+    Cycles(u64),
+    // the next instruction is BRK
+    Brk,
+    // an instruction jumped or branched to itself, which is how test
+    // programs usually halt
+    SelfLoop,
+    Write(u16),
+}
+
+impl fmt::Display for StopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopCondition::Pc(addr) => write!(f, "PC reached {addr:#06x}"),
+            StopCondition::Instructions(n) => write!(f, "{n} instructions executed"),
+            StopCondition::Cycles(n) => write!(f, "{n} cycles elapsed"),
+            StopCondition::Brk => write!(f, "BRK"),
+            StopCondition::SelfLoop => write!(f, "self-loop"),
+            StopCondition::Write(addr) => write!(f, "write to {addr:#06x}"),
+        }
+    }
+}
+
+// Memory that notes writes to a few watched addresses, so a run can stop
+// on them.
+pub struct WriteWatch<M: Mem> {
+    pub inner: M,
+    watched: Vec<u16>,
+    hit: Option<u16>,
+}
+
+impl<M: Mem> WriteWatch<M> {
+    pub fn new(inner: M) -> Self {
+        WriteWatch {
+            inner,
+            watched: Vec::new(),
+            hit: None,
+        }
+    }
+
+    pub fn watch(&mut self, addr: u16) {
+        if !self.watched.contains(&addr) {
+            self.watched.push(addr);
+        }
+    }
+
+    // the first watched address written since the last call
+    pub fn take_hit(&mut self) -> Option<u16> {
+        self.hit.take()
+    }
+}
+
+impl<M: Mem> Mem for WriteWatch<M> {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.inner.mem_read(addr)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.inner.mem_peek(addr)
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        if self.hit.is_none() && self.watched.contains(&addr) {
+            self.hit = Some(addr);
+        }
+        self.inner.mem_write(addr, data);
+    }
+
+    fn power_on(&mut self) {
+        self.inner.power_on();
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn clock(&mut self, cycles: u64) {
+        self.inner.clock(cycles);
+    }
+
+    fn irq(&self) -> bool {
+        self.inner.irq()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stopped {
+    pub condition: StopCondition,
+    // counted from the start of this run
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+fn check<M: Mem>(
+    cpu: &mut CPU<WriteWatch<M>>,
+    conditions: &[StopCondition],
+    last: Option<&StepInfo>,
+    instructions: u64,
+    cycles: u64,
+) -> Option<StopCondition> {
+    let written = cpu.bus.take_hit();
+    conditions
+        .iter()
+        .copied()
+        .find(|condition| match *condition {
+            StopCondition::Pc(addr) => cpu.program_counter == addr,
+            StopCondition::Instructions(n) => instructions >= n,
+            StopCondition::Cycles(n) => cycles >= n,
+            StopCondition::Brk => cpu.mem_peek(cpu.program_counter) == 0x00,
+            StopCondition::SelfLoop => last.is_some_and(|info| info.counter == cpu.program_counter),
+            StopCondition::Write(addr) => written == Some(addr),
+        })
+}
+
+// Runs until one of the conditions holds, checking them in order before the
+// first instruction and after every one. A CPU error ends the run early.
+pub fn run<M: Mem>(
+    cpu: &mut CPU<WriteWatch<M>>,
+    conditions: &[StopCondition],
+) -> Result<Stopped, CpuError> {
+    for condition in conditions {
+        if let StopCondition::Write(addr) = condition {
+            cpu.bus.watch(*addr);
+        }
+    }
+    let start_cycles = cpu.cycles;
+    if let Some(condition) = check(cpu, conditions, None, 0, 0) {
+        return Ok(Stopped {
+            condition,
+            instructions: 0,
+            cycles: 0,
+        });
+    }
+
+    let mut instructions = 0;
+    let mut stopped = None;
+    cpu.run_until(|cpu, info| {
+        instructions += 1;
+        let cycles = cpu.cycles - start_cycles;
+        match check(cpu, conditions, Some(info), instructions, cycles) {
+            Some(condition) => {
+                stopped = Some(Stopped {
+                    condition,
+                    instructions,
+                    cycles,
+                });
+                RunControl::Stop
+            }
+            None => RunControl::Continue,
+        }
+    })?;
+    Ok(stopped.expect("run_until only returns once a condition holds"))
+}
+
+// A:00 X:00 Y:00 P:24 SP:FD PC:C000 CYC:7
+pub fn registers<M: Mem>(cpu: &CPU<M>) -> String {
+    format!(
+        "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PC:{:04X} CYC:{}",
+        cpu.reg_a, cpu.reg_x, cpu.reg_y, cpu.status, cpu.stp, cpu.program_counter, cpu.cycles
+    )
+}
+
+// Sixteen bytes a line, each line starting with its address.
+pub fn hex_dump<M: Mem>(mem: &M, start: u16, end: u16) -> String {
+    let mut out = String::new();
+    let mut addr = start as u32;
+    while addr <= end as u32 {
+        let line_end = (addr + 15).min(end as u32);
+        let bytes: Vec<String> = (addr..=line_end)
+            .map(|a| format!("{:02X}", mem.mem_peek(a as u16)))
+            .collect();
+        out.push_str(&format!("{:04X}: {}\n", addr, bytes.join(" ")));
+        addr = line_end + 1;
+    }
+    out
+}
@@ -0,0 +1,263 @@
+use crate::constants::AddressingMode;
+use crate::cpu::CPU;
+use crate::mem::Mem;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+// One traced instruction, captured before it executes.
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub counter: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    // operand as nestest.log prints it, with the resolved address and value
+    pub operand: String,
+    pub reg_a: u8,
+    pub reg_x: u8,
+    pub reg_y: u8,
+    pub status: u8,
+    pub stp: u8,
+    pub cycles: u64,
+}
+
+impl TraceEntry {
+    // Scanline and dot of an NTSC PPU powered on with the CPU: three dots per
+    // CPU cycle, 341 dots per scanline and 262 scanlines per frame.
+    pub fn ppu_position(&self) -> (u64, u64) {
+        let dots = self.cycles * 3;
+        ((dots / 341) % 262, dots % 341)
+    }
+}
+
+// Formats the entry as a nestest.log line:
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let asm = format!(
+            "{:04X}  {:8} {:>4} {}",
+            self.counter,
+            hex.join(" "),
+            self.mnemonic,
+            self.operand
+        );
+        let (scanline, dot) = self.ppu_position();
+        write!(
+            f,
+            "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:3},{:3} CYC:{}",
+            asm.trim_end(),
+            self.reg_a,
+            self.reg_x,
+            self.reg_y,
+            self.status,
+            self.stp,
+            scanline,
+            dot,
+            self.cycles
+        )
+    }
+}
+
+// Something worth flagging that the CPU carries on through regardless, the
+// way the hardware would. Only reported when CPU::diagnostics is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostic {
+    // a push wrapped SP from $00 to $FF
+    StackOverflow { counter: u16 },
+    // a pull wrapped SP from $FF to $00
+    StackUnderflow { counter: u16 },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::StackOverflow { counter } => {
+                write!(f, "Stack overflow at {counter:#06x}")
+            }
+            Diagnostic::StackUnderflow { counter } => {
+                write!(f, "Stack underflow at {counter:#06x}")
+            }
+        }
+    }
+}
+
+pub trait TraceSink {
+    fn trace(&mut self, entry: &TraceEntry);
+
+    fn diagnostic(&mut self, _diagnostic: &Diagnostic) {}
+}
+
+impl<F: FnMut(&TraceEntry)> TraceSink for F {
+    fn trace(&mut self, entry: &TraceEntry) {
+        self(entry)
+    }
+}
+
+// Writes nestest-style lines to a file, stdout or any other writer. The first
+// write error stops the trace and is kept for take_error().
+pub struct WriterSink<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(out: W) -> Self {
+        WriterSink { out, error: None }
+    }
+
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl<W: Write> WriterSink<W> {
+    fn write_line(&mut self, line: &dyn fmt::Display) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl<W: Write> TraceSink for WriterSink<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        self.write_line(entry);
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.write_line(diagnostic);
+    }
+}
+
+// Forwards only instructions whose address falls inside the range.
+pub struct AddressFilter<S: TraceSink> {
+    range: RangeInclusive<u16>,
+    inner: S,
+}
+
+impl<S: TraceSink> AddressFilter<S> {
+    pub fn new(range: RangeInclusive<u16>, inner: S) -> Self {
+        AddressFilter { range, inner }
+    }
+}
+
+impl<S: TraceSink> TraceSink for AddressFilter<S> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.range.contains(&entry.counter) {
+            self.inner.trace(entry);
+        }
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.inner.diagnostic(diagnostic);
+    }
+}
+
+fn peek_u16_zero_page<M: Mem>(cpu: &CPU<M>, ptr: u8) -> u16 {
+    let lo = cpu.mem_peek(ptr as u16);
+    let hi = cpu.mem_peek(ptr.wrapping_add(1) as u16);
+    (hi as u16) << 8 | (lo as u16)
+}
+
+fn peek_u16<M: Mem>(cpu: &CPU<M>, addr: u16) -> u16 {
+    let lo = cpu.mem_peek(addr);
+    let hi = cpu.mem_peek(addr.wrapping_add(1));
+    (hi as u16) << 8 | (lo as u16)
+}
+
+pub fn trace<M: Mem>(cpu: &CPU<M>) -> TraceEntry {
+    let counter = cpu.program_counter;
+    let op = cpu.variant().opcode(cpu.mem_peek(counter));
+    let bytes: Vec<u8> = (0..op.bytes as u16)
+        .map(|i| cpu.mem_peek(counter.wrapping_add(i)))
+        .collect();
+    let arg8 = bytes.get(1).copied().unwrap_or(0);
+    let arg16 = (bytes.get(2).copied().unwrap_or(0) as u16) << 8 | arg8 as u16;
+
+    let operand = match op.add_mode {
+        AddressingMode::NoneAddressing => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02x}", arg8),
+        AddressingMode::Relative => {
+            let target = counter.wrapping_add(2).wrapping_add(arg8 as i8 as u16);
+            format!("${:04x}", target)
+        }
+        AddressingMode::ZeroPage => {
+            format!("${:02x} = {:02x}", arg8, cpu.mem_peek(arg8 as u16))
+        }
+        AddressingMode::ZeroPage_X => {
+            let addr = arg8.wrapping_add(cpu.reg_x) as u16;
+            format!("${:02x},X @ {:02x} = {:02x}", arg8, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::ZeroPage_Y => {
+            let addr = arg8.wrapping_add(cpu.reg_y) as u16;
+            format!("${:02x},Y @ {:02x} = {:02x}", arg8, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Absolute => {
+            if op.name == "JMP" || op.name == "JSR" {
+                format!("${:04x}", arg16)
+            } else {
+                format!("${:04x} = {:02x}", arg16, cpu.mem_peek(arg16))
+            }
+        }
+        AddressingMode::Absolute_X => {
+            let addr = arg16.wrapping_add(cpu.reg_x as u16);
+            format!("${:04x},X @ {:04x} = {:02x}", arg16, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Absolute_Y => {
+            let addr = arg16.wrapping_add(cpu.reg_y as u16);
+            format!("${:04x},Y @ {:04x} = {:02x}", arg16, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Indirect => {
+            let lo = cpu.mem_peek(arg16);
+            let hi = cpu.mem_peek(cpu.variant().indirect_jump_hi(arg16));
+            format!("(${:04x}) = {:02x}{:02x}", arg16, hi, lo)
+        }
+        AddressingMode::Indirect_X => {
+            let ptr = arg8.wrapping_add(cpu.reg_x);
+            let addr = peek_u16_zero_page(cpu, ptr);
+            format!(
+                "(${:02x},X) @ {:02x} = {:04x} = {:02x}",
+                arg8,
+                ptr,
+                addr,
+                cpu.mem_peek(addr)
+            )
+        }
+        AddressingMode::Indirect_Y => {
+            let base = peek_u16_zero_page(cpu, arg8);
+            let addr = base.wrapping_add(cpu.reg_y as u16);
+            format!(
+                "(${:02x}),Y = {:04x} @ {:04x} = {:02x}",
+                arg8,
+                base,
+                addr,
+                cpu.mem_peek(addr)
+            )
+        }
+        AddressingMode::ZeroPage_Indirect => {
+            let addr = peek_u16_zero_page(cpu, arg8);
+            format!("(${:02x}) = {:04x} = {:02x}", arg8, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Absolute_X_Indirect => {
+            let ptr = arg16.wrapping_add(cpu.reg_x as u16);
+            format!("(${:04x},X) @ {:04x} = {:04x}", arg16, ptr, peek_u16(cpu, ptr))
+        }
+    };
+
+    TraceEntry {
+        counter,
+        bytes,
+        mnemonic: op.name,
+        operand: operand.to_ascii_uppercase(),
+        reg_a: cpu.reg_a,
+        reg_x: cpu.reg_x,
+        reg_y: cpu.reg_y,
+        status: cpu.status,
+        stp: cpu.stp,
+        cycles: cpu.cycles,
+    }
+}
@@ -0,0 +1,181 @@
+// Pointer fetches must wrap the way the hardware does: inside the zero page
+// for zero-page pointers, inside the page for NMOS JMP ($xxFF), and around
+// $FFFF for everything else.
+
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+// Runs one instruction at `pc` in both atomic and cycle-stepped mode and
+// checks both agree before handing back the atomic CPU.
+fn run_one(variant: CpuVariant, pc: u16, setup: impl Fn(&mut CPU<FlatMemory>)) -> CPU<FlatMemory> {
+    let mut cpus: Vec<CPU<FlatMemory>> = [false, true]
+        .iter()
+        .map(|&cycle_stepped| {
+            let mut cpu = CPU::with_variant(FlatMemory::new(), variant);
+            cpu.program_counter = pc;
+            cpu.cycle_stepped = cycle_stepped;
+            setup(&mut cpu);
+            cpu.step().expect("instruction faulted");
+            cpu
+        })
+        .collect();
+    let stepped = cpus.pop().unwrap();
+    let atomic = cpus.pop().unwrap();
+    assert_eq!(atomic.program_counter, stepped.program_counter);
+    assert_eq!(atomic.reg_a, stepped.reg_a);
+    atomic
+}
+
+fn write(cpu: &mut CPU<FlatMemory>, addr: u16, bytes: &[u8]) {
+    for (i, byte) in bytes.iter().enumerate() {
+        cpu.mem_write(addr.wrapping_add(i as u16), *byte);
+    }
+}
+
+#[test]
+fn nmos_jmp_indirect_wraps_within_page() {
+    for variant in [CpuVariant::Ricoh2A03, CpuVariant::Nmos6502] {
+        let cpu = run_one(variant, 0x0200, |cpu| {
+            write(cpu, 0x0200, &[0x6c, 0xff, 0x10]);
+            write(cpu, 0x10ff, &[0x34]);
+            write(cpu, 0x1000, &[0x12]);
+            write(cpu, 0x1100, &[0x56]);
+        });
+        assert_eq!(cpu.program_counter, 0x1234, "{:?}", variant);
+    }
+}
+
+#[test]
+fn cmos_jmp_indirect_crosses_page() {
+    let cpu = run_one(CpuVariant::Cmos65C02, 0x0200, |cpu| {
+        write(cpu, 0x0200, &[0x6c, 0xff, 0x10]);
+        write(cpu, 0x10ff, &[0x34]);
+        write(cpu, 0x1000, &[0x12]);
+        write(cpu, 0x1100, &[0x56]);
+    });
+    assert_eq!(cpu.program_counter, 0x5634);
+}
+
+#[test]
+fn jmp_indirect_through_ffff() {
+    let nmos = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        write(cpu, 0x0200, &[0x6c, 0xff, 0xff]);
+        write(cpu, 0xffff, &[0x34]);
+        write(cpu, 0xff00, &[0x12]);
+        write(cpu, 0x0000, &[0x56]);
+    });
+    assert_eq!(nmos.program_counter, 0x1234);
+
+    let cmos = run_one(CpuVariant::Cmos65C02, 0x0200, |cpu| {
+        write(cpu, 0x0200, &[0x6c, 0xff, 0xff]);
+        write(cpu, 0xffff, &[0x34]);
+        write(cpu, 0xff00, &[0x12]);
+        write(cpu, 0x0000, &[0x56]);
+    });
+    assert_eq!(cmos.program_counter, 0x5634);
+}
+
+#[test]
+fn indexed_indirect_pointer_wraps_in_zero_page() {
+    // LDA ($FF,X) with X = 0: pointer bytes at $FF and $00
+    let cpu = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        write(cpu, 0x0200, &[0xa1, 0xff]);
+        write(cpu, 0x00ff, &[0x34]);
+        write(cpu, 0x0000, &[0x12]);
+        write(cpu, 0x1234, &[0x77]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+}
+
+#[test]
+fn indexed_indirect_base_plus_x_wraps_in_zero_page() {
+    // LDA ($80,X) with X = $80: pointer at $00, not $100
+    let cpu = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        cpu.reg_x = 0x80;
+        write(cpu, 0x0200, &[0xa1, 0x80]);
+        write(cpu, 0x0000, &[0x34, 0x12]);
+        write(cpu, 0x0100, &[0x78, 0x56]);
+        write(cpu, 0x1234, &[0x77]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+}
+
+#[test]
+fn indirect_indexed_pointer_wraps_in_zero_page() {
+    // LDA ($FF),Y with Y = 1: pointer bytes at $FF and $00
+    let cpu = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        cpu.reg_y = 0x01;
+        write(cpu, 0x0200, &[0xb1, 0xff]);
+        write(cpu, 0x00ff, &[0x34]);
+        write(cpu, 0x0000, &[0x12]);
+        write(cpu, 0x1235, &[0x77]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+}
+
+#[test]
+fn indirect_indexed_address_wraps_past_ffff() {
+    // LDA ($10),Y with ($10) = $FFF0 and Y = $20
+    let cpu = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        cpu.reg_y = 0x20;
+        write(cpu, 0x0200, &[0xb1, 0x10]);
+        write(cpu, 0x0010, &[0xf0, 0xff]);
+        write(cpu, 0x1010, &[0x66]);
+    });
+    // $FFF0 + $20 = $0010, which holds the pointer's low byte
+    assert_eq!(cpu.reg_a, 0xf0);
+}
+
+#[test]
+fn zero_page_indexed_wraps_in_zero_page() {
+    // LDA $F0,X with X = $20 reads $10
+    let cpu = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        cpu.reg_x = 0x20;
+        write(cpu, 0x0200, &[0xb5, 0xf0]);
+        write(cpu, 0x0010, &[0x77]);
+        write(cpu, 0x0110, &[0x66]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+}
+
+#[test]
+fn absolute_indexed_wraps_past_ffff() {
+    // LDA $FFF0,X with X = $20 reads $0010
+    let cpu = run_one(CpuVariant::Nmos6502, 0x0200, |cpu| {
+        cpu.reg_x = 0x20;
+        write(cpu, 0x0200, &[0xbd, 0xf0, 0xff]);
+        write(cpu, 0x0010, &[0x77]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+}
+
+#[test]
+fn cmos_zero_page_indirect_wraps_in_zero_page() {
+    // LDA ($FF)
+    let cpu = run_one(CpuVariant::Cmos65C02, 0x0200, |cpu| {
+        write(cpu, 0x0200, &[0xb2, 0xff]);
+        write(cpu, 0x00ff, &[0x34]);
+        write(cpu, 0x0000, &[0x12]);
+        write(cpu, 0x1234, &[0x77]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+}
+
+#[test]
+fn operand_fetch_wraps_past_ffff() {
+    // LDA $1234 with the opcode at $FFFE and the operand at $FFFF/$0000
+    let cpu = run_one(CpuVariant::Nmos6502, 0xfffe, |cpu| {
+        write(cpu, 0xfffe, &[0xad, 0x34, 0x12]);
+        write(cpu, 0x1234, &[0x77]);
+    });
+    assert_eq!(cpu.reg_a, 0x77);
+    assert_eq!(cpu.program_counter, 0x0001);
+}
+
+#[test]
+fn read_u16_wraps_past_ffff() {
+    let mut memory = FlatMemory::new();
+    memory.mem_write(0xffff, 0x34);
+    memory.mem_write(0x0000, 0x12);
+    assert_eq!(memory.mem_read_u16(0xffff), 0x1234);
+}
@@ -0,0 +1,236 @@
+use nes_emulator::asm::{AsmErrorKind, assemble, assemble_program};
+use nes_emulator::bus::Bus;
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+// the snake game as main.rs used to embed it, hand-assembled
+const SNAKE: [u8; 309] = [
+    0x20, 0x06, 0x06, 0x20, 0x38, 0x06, 0x20, 0x0d, 0x06, 0x20, 0x2a, 0x06, 0x60, 0xa9, 0x02, 0x85,
+    0x02, 0xa9, 0x04, 0x85, 0x03, 0xa9, 0x11, 0x85, 0x10, 0xa9, 0x10, 0x85, 0x12, 0xa9, 0x0f, 0x85,
+    0x14, 0xa9, 0x04, 0x85, 0x11, 0x85, 0x13, 0x85, 0x15, 0x60, 0xa5, 0xfe, 0x85, 0x00, 0xa5, 0xfe,
+    0x29, 0x03, 0x18, 0x69, 0x02, 0x85, 0x01, 0x60, 0x20, 0x4d, 0x06, 0x20, 0x8d, 0x06, 0x20, 0xc3,
+    0x06, 0x20, 0x19, 0x07, 0x20, 0x20, 0x07, 0x20, 0x2d, 0x07, 0x4c, 0x38, 0x06, 0xa5, 0xff, 0xc9,
+    0x77, 0xf0, 0x0d, 0xc9, 0x64, 0xf0, 0x14, 0xc9, 0x73, 0xf0, 0x1b, 0xc9, 0x61, 0xf0, 0x22, 0x60,
+    0xa9, 0x04, 0x24, 0x02, 0xd0, 0x26, 0xa9, 0x01, 0x85, 0x02, 0x60, 0xa9, 0x08, 0x24, 0x02, 0xd0,
+    0x1b, 0xa9, 0x02, 0x85, 0x02, 0x60, 0xa9, 0x01, 0x24, 0x02, 0xd0, 0x10, 0xa9, 0x04, 0x85, 0x02,
+    0x60, 0xa9, 0x02, 0x24, 0x02, 0xd0, 0x05, 0xa9, 0x08, 0x85, 0x02, 0x60, 0x60, 0x20, 0x94, 0x06,
+    0x20, 0xa8, 0x06, 0x60, 0xa5, 0x00, 0xc5, 0x10, 0xd0, 0x0d, 0xa5, 0x01, 0xc5, 0x11, 0xd0, 0x07,
+    0xe6, 0x03, 0xe6, 0x03, 0x20, 0x2a, 0x06, 0x60, 0xa2, 0x02, 0xb5, 0x10, 0xc5, 0x10, 0xd0, 0x06,
+    0xb5, 0x11, 0xc5, 0x11, 0xf0, 0x09, 0xe8, 0xe8, 0xe4, 0x03, 0xf0, 0x06, 0x4c, 0xaa, 0x06, 0x4c,
+    0x35, 0x07, 0x60, 0xa6, 0x03, 0xca, 0x8a, 0xb5, 0x10, 0x95, 0x12, 0xca, 0x10, 0xf9, 0xa5, 0x02,
+    0x4a, 0xb0, 0x09, 0x4a, 0xb0, 0x19, 0x4a, 0xb0, 0x1f, 0x4a, 0xb0, 0x2f, 0xa5, 0x10, 0x38, 0xe9,
+    0x20, 0x85, 0x10, 0x90, 0x01, 0x60, 0xc6, 0x11, 0xa9, 0x01, 0xc5, 0x11, 0xf0, 0x28, 0x60, 0xe6,
+    0x10, 0xa9, 0x1f, 0x24, 0x10, 0xf0, 0x1f, 0x60, 0xa5, 0x10, 0x18, 0x69, 0x20, 0x85, 0x10, 0xb0,
+    0x01, 0x60, 0xe6, 0x11, 0xa9, 0x06, 0xc5, 0x11, 0xf0, 0x0c, 0x60, 0xc6, 0x10, 0xa5, 0x10, 0x29,
+    0x1f, 0xc9, 0x1f, 0xf0, 0x01, 0x60, 0x4c, 0x35, 0x07, 0xa0, 0x00, 0xa5, 0xfe, 0x91, 0x00, 0x60,
+    0xa6, 0x03, 0xa9, 0x00, 0x81, 0x10, 0xa2, 0x00, 0xa9, 0x01, 0x81, 0x10, 0x60, 0xa6, 0xff, 0xea,
+    0xea, 0xca, 0xd0, 0xfb, 0x60,
+];
+
+fn error_of(source: &str) -> (usize, AsmErrorKind) {
+    let e = assemble(source).unwrap_err();
+    (e.line, e.kind)
+}
+
+#[test]
+fn snake_source_matches_the_hand_assembled_bytes() {
+    let code = assemble(include_str!("../programs/snake.asm")).unwrap();
+    assert_eq!(code, SNAKE);
+}
+
+#[test]
+fn snake_game_over_ends_on_brk() {
+    // with no input the snake runs into the right wall
+    let mut cpu = CPU::new(Bus::new());
+    cpu.power_on();
+    cpu.load(assemble(include_str!("../programs/snake.asm")).unwrap());
+    let mut steps = 0;
+    while cpu.mem_peek(cpu.program_counter) != 0x00 {
+        assert!(steps < 1_000_000, "no game over");
+        cpu.mem_write(0xfe, 7);
+        cpu.step().unwrap();
+        steps += 1;
+    }
+}
+
+#[test]
+fn encodes_every_addressing_mode() {
+    let source = "
+        nop
+        asl
+        asl a
+        lda #$12
+        lda $12
+        lda $12,x
+        ldx $12,y
+        lda $1234
+        lda $1234,X
+        lda $1234,Y
+        jmp ($1234)
+        lda ($34,x)
+        lda ( $34 ), y
+    ";
+    assert_eq!(
+        assemble(source).unwrap(),
+        [
+            0xea, 0x0a, 0x0a, 0xa9, 0x12, 0xa5, 0x12, 0xb5, 0x12, 0xb6, 0x12, 0xad, 0x34, 0x12,
+            0xbd, 0x34, 0x12, 0xb9, 0x34, 0x12, 0x6c, 0x34, 0x12, 0xa1, 0x34, 0xb1, 0x34,
+        ]
+    );
+}
+
+#[test]
+fn zero_page_operand_without_zero_page_form_widens() {
+    // LDA has no zp,Y form; STX has no abs,Y form
+    assert_eq!(assemble("lda $12,y").unwrap(), [0xb9, 0x12, 0x00]);
+    assert_eq!(
+        error_of("stx $1234,y").1,
+        AsmErrorKind::ValueOutOfRange(0x1234)
+    );
+}
+
+#[test]
+fn forward_references_get_absolute_addressing() {
+    let source = "
+        lda zp
+        lda zp
+        .define zp $20
+    ";
+    // the first use is not known yet in the first pass
+    let code = assemble(source).unwrap();
+    assert_eq!(code, [0xad, 0x20, 0x00, 0xad, 0x20, 0x00]);
+
+    let code = assemble(".define zp $20\n lda zp").unwrap();
+    assert_eq!(code, [0xa5, 0x20]);
+}
+
+#[test]
+fn labels_branches_and_local_labels() {
+    let source = "
+    first:
+        ldx #3
+    @loop:
+        dex
+        bne @loop
+        beq second
+    second:
+    @loop:
+        jmp @loop
+    ";
+    let program = assemble_program(CpuVariant::Ricoh2A03, source).unwrap();
+    assert_eq!(
+        program.bytes,
+        [0xa2, 0x03, 0xca, 0xd0, 0xfd, 0xf0, 0x00, 0x4c, 0x07, 0x06]
+    );
+    assert_eq!(program.symbols["first@loop"], 0x0602);
+    assert_eq!(program.symbols["second@loop"], 0x0607);
+}
+
+#[test]
+fn directives_and_expressions() {
+    let source = "
+        .org $8000
+        COUNT = 2 + 3 * 4
+        .define PTR $1234
+    table:
+        .byte COUNT, <PTR, >PTR, %1010, 'A', \"hi;\", -1
+        .word table, PTR >> 4, (1 + 2) * 3
+        lda #<(table + COUNT)
+        .org * + 2
+        .byte $ff
+    ";
+    let program = assemble_program(CpuVariant::Ricoh2A03, source).unwrap();
+    assert_eq!(program.origin, 0x8000);
+    assert_eq!(
+        program.bytes,
+        [
+            14, 0x34, 0x12, 0b1010, b'A', b'h', b'i', b';', 0xff, 0x00, 0x80, 0x23, 0x01, 0x09,
+            0x00, 0xa9, 0x0e, 0x00, 0x00, 0xff,
+        ]
+    );
+}
+
+#[test]
+fn cmos_modes_need_the_cmos_variant() {
+    let source = "lda ($12)\n jmp ($1234,x)\n bra *";
+    let program = assemble_program(CpuVariant::Cmos65C02, source).unwrap();
+    assert_eq!(program.bytes, [0xb2, 0x12, 0x7c, 0x34, 0x12, 0x80, 0xfe]);
+
+    assert_eq!(
+        error_of("lda ($12)"),
+        (1, AsmErrorKind::InvalidAddressing("LDA".to_string()))
+    );
+    assert_eq!(
+        error_of("bra *"),
+        (1, AsmErrorKind::UnknownInstruction("BRA".to_string()))
+    );
+}
+
+#[test]
+fn errors_carry_line_numbers() {
+    assert_eq!(
+        error_of("nop\n lda missing"),
+        (2, AsmErrorKind::UndefinedSymbol("missing".to_string()))
+    );
+    assert_eq!(
+        error_of("a:\n a:"),
+        (2, AsmErrorKind::DuplicateSymbol("a".to_string()))
+    );
+    assert_eq!(error_of("\n\n lda #$100").0, 3);
+    assert_eq!(
+        error_of(".bogus 1").1,
+        AsmErrorKind::UnknownDirective(".bogus".to_string())
+    );
+    assert!(matches!(error_of("lda (1").1, AsmErrorKind::Syntax(_)));
+
+    let far = format!("start:\n{}\n beq start", " nop\n".repeat(200));
+    assert_eq!(error_of(&far), (203, AsmErrorKind::BranchOutOfRange(-202)));
+    assert_eq!(
+        error_of("a = b\n b = a").1,
+        AsmErrorKind::UndefinedSymbol("b".to_string())
+    );
+}
+
+#[test]
+fn org_back_over_emitted_bytes_is_an_error() {
+    assert_eq!(
+        error_of(".org $0600\n lda #1\n sta $10\n .org $0602\n nop"),
+        (5, AsmErrorKind::Overlap(0x0602))
+    );
+    // running into a later block counts too
+    assert_eq!(
+        error_of(".org $0700\n nop\n .org $06FF\n nop\n nop"),
+        (5, AsmErrorKind::Overlap(0x0700))
+    );
+    // moving back into a gap is fine
+    let program =
+        assemble_program(CpuVariant::Ricoh2A03, ".org $0700\n nop\n .org $0600\n nop").unwrap();
+    assert_eq!(program.origin, 0x0600);
+    assert_eq!(program.bytes[0], 0xea);
+    assert_eq!(program.bytes[0x100], 0xea);
+}
+
+#[test]
+fn assembled_program_runs() {
+    let source = "
+        ldx #0
+    @copy:
+        lda message,x
+        beq @done
+        sta $0200,x
+        inx
+        bne @copy
+    @done:
+        brk
+    message:
+        .byte \"6502\", 0
+    ";
+    let program = assemble_program(CpuVariant::Ricoh2A03, source).unwrap();
+    let mut cpu = CPU::new(FlatMemory::new());
+    cpu.load(program.bytes);
+    while cpu.program_counter as i64 != program.symbols["@done"] {
+        cpu.step().unwrap();
+    }
+    let copied: Vec<u8> = (0..4).map(|i| cpu.mem_peek(0x0200 + i)).collect();
+    assert_eq!(copied, b"6502");
+}
@@ -0,0 +1,33 @@
+use nes_emulator::bus::Bus;
+use nes_emulator::mem::Mem;
+
+#[test]
+fn ram_mirrors_every_800() {
+    let mut bus = Bus::new();
+    for base in [0x0000, 0x0800, 0x1000, 0x1800] {
+        let value = (base >> 8) as u8 + 1;
+        bus.mem_write(base + 0x0123, value);
+        for mirror in [0x0000, 0x0800, 0x1000, 0x1800] {
+            assert_eq!(bus.mem_read(mirror + 0x0123), value);
+        }
+    }
+    // the last byte of the last mirror is the last byte of RAM
+    bus.mem_write(0x07FF, 0x55);
+    assert_eq!(bus.mem_read(0x1FFF), 0x55);
+}
+
+#[test]
+fn ppu_registers_mirror_every_8() {
+    let mut bus = Bus::new();
+    for register in 0..8 {
+        bus.mem_write(0x2000 + register, 0x10 + register as u8);
+    }
+    for addr in (0x2000..=0x3FFF).step_by(0x0123) {
+        assert_eq!(bus.mem_read(addr), 0x10 + (addr & 7) as u8);
+    }
+    bus.mem_write(0x3FFF, 0x77);
+    assert_eq!(bus.mem_read(0x2007), 0x77);
+    // RAM and the APU registers are untouched
+    assert_eq!(bus.mem_read(0x1FFF), 0);
+    assert_eq!(bus.mem_read(0x4000), 0);
+}
@@ -0,0 +1,207 @@
+use nes_emulator::bus::Bus;
+use nes_emulator::cartridge::{
+    ConsoleType, HeaderFormat, Mirroring, Region, Rom, RomError, Timing,
+};
+use nes_emulator::mem::Mem;
+
+// An iNES image whose PRG bytes count up from the bank number, so reads can
+// tell banks apart.
+fn ines(prg_banks: u8, chr_banks: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+    let mut raw = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks, flags6, flags7];
+    raw.resize(16, 0);
+    if flags6 & 0b100 != 0 {
+        raw.extend((0..512).map(|i| (i % 251) as u8));
+    }
+    for bank in 0..prg_banks {
+        raw.extend(std::iter::repeat_n(bank, 0x4000));
+    }
+    raw.extend(std::iter::repeat_n(0xC7, chr_banks as usize * 0x2000));
+    raw
+}
+
+#[test]
+fn parses_the_header() {
+    let rom = Rom::new(&ines(2, 1, 0b0001_0011, 0b0100_0000)).unwrap();
+    assert_eq!(rom.mapper, 0x41);
+    assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+    assert!(rom.battery);
+    assert!(rom.trainer.is_none());
+    assert_eq!(rom.prg_rom.len(), 0x8000);
+    assert_eq!(rom.chr_rom.len(), 0x2000);
+    assert!(!rom.chr_is_ram);
+
+    let rom = Rom::new(&ines(1, 1, 0b1001, 0)).unwrap();
+    assert_eq!(rom.screen_mirroring, Mirroring::FourScreen);
+    let rom = Rom::new(&ines(1, 1, 0, 0)).unwrap();
+    assert_eq!(rom.screen_mirroring, Mirroring::Horizontal);
+    assert!(!rom.battery);
+}
+
+#[test]
+fn no_chr_rom_means_chr_ram() {
+    let rom = Rom::new(&ines(1, 0, 0, 0)).unwrap();
+    assert!(rom.chr_is_ram);
+    assert_eq!(rom.chr_rom, vec![0; 0x2000]);
+}
+
+#[test]
+fn trainer_sits_before_prg_rom() {
+    let rom = Rom::new(&ines(1, 1, 0b100, 0)).unwrap();
+    let trainer = rom.trainer.as_ref().unwrap();
+    assert_eq!(trainer.len(), 512);
+    assert_eq!(trainer[300], (300 % 251) as u8);
+    assert_eq!(rom.prg_rom[0], 0);
+
+    let bus = Bus::with_rom(rom).unwrap();
+    assert_eq!(bus.mem_peek(0x7000), 0);
+    assert_eq!(bus.mem_peek(0x712c), (300 % 251) as u8);
+}
+
+#[test]
+fn junk_in_old_headers_is_ignored() {
+    let mut raw = ines(1, 1, 0b0001_0000, b'D');
+    raw[7..16].copy_from_slice(b"DiskDude!");
+    let rom = Rom::new(&raw).unwrap();
+    assert_eq!(rom.format, HeaderFormat::Archaic);
+    assert_eq!(rom.mapper, 1);
+    assert_eq!(rom.timing, Timing::Ntsc);
+}
+
+#[test]
+fn ines_ram_size_and_pal_bit() {
+    let mut raw = ines(1, 1, 0b10, 0);
+    raw[8] = 2;
+    raw[9] = 1;
+    let rom = Rom::new(&raw).unwrap();
+    assert_eq!(rom.format, HeaderFormat::INes);
+    assert_eq!((rom.prg_ram_size, rom.prg_nvram_size), (0, 0x4000));
+    assert_eq!(rom.timing, Timing::Pal);
+    assert_eq!(Bus::with_rom(rom).unwrap().region, Region::Pal);
+}
+
+#[test]
+fn parses_nes2_fields() {
+    let mut raw = ines(1, 0, 0x10, 0x29);
+    raw[8] = 0x31;
+    raw[10] = 0x70;
+    raw[11] = 0x07;
+    raw[12] = 3;
+    raw[13] = 0x21;
+    raw[15] = 0x01;
+    let rom = Rom::new(&raw).unwrap();
+    assert_eq!(rom.format, HeaderFormat::Nes2);
+    assert_eq!(rom.mapper, 0x121);
+    assert_eq!(rom.submapper, 3);
+    assert_eq!((rom.prg_ram_size, rom.prg_nvram_size), (0, 0x2000));
+    assert_eq!((rom.chr_ram_size, rom.chr_nvram_size), (0x2000, 0));
+    assert_eq!(rom.chr_rom.len(), 0x2000);
+    assert_eq!(rom.timing, Timing::Dendy);
+    assert_eq!(
+        rom.console,
+        ConsoleType::VsSystem {
+            ppu: 1,
+            hardware: 2
+        }
+    );
+    assert_eq!(rom.expansion_device, 1);
+}
+
+#[test]
+fn nes2_pal_header_sets_the_bus_region_and_clock() {
+    let mut raw = ines(1, 1, 0, 0x08);
+    raw[12] = 1;
+    let rom = Rom::new(&raw).unwrap();
+    assert_eq!(rom.timing, Timing::Pal);
+    let bus = Bus::with_rom(rom).unwrap();
+    assert_eq!(bus.region, Region::Pal);
+    assert_eq!(bus.region.cpu_clock_hz(), 1_662_607);
+    assert_eq!(Region::default().cpu_clock_hz(), 1_789_773);
+}
+
+#[test]
+fn nes2_exponent_multiplier_sizes() {
+    // 2^12 * 3 bytes of PRG ROM
+    let mut raw = ines(1, 1, 0, 0x08);
+    raw[4] = 12 << 2 | 1;
+    raw[9] = 0x0F;
+    let rom = Rom::new(&raw).unwrap();
+    assert_eq!(rom.prg_rom.len(), 0x3000);
+    assert_eq!(rom.chr_rom.len(), 0x2000);
+}
+
+#[test]
+fn nes2_tag_with_impossible_sizes_falls_back() {
+    // PRG size high nibble 1 asks for far more data than the file has
+    let mut raw = ines(1, 1, 0x10, 0x28);
+    raw[9] = 0x01;
+    let rom = Rom::new(&raw).unwrap();
+    assert_eq!(rom.format, HeaderFormat::Archaic);
+    assert_eq!(rom.mapper, 1);
+    assert_eq!(rom.prg_rom.len(), 0x4000);
+}
+
+#[test]
+fn bus_sizes_prg_ram_from_the_header() {
+    // NES 2.0 with no PRG RAM: $6000 reads back 0
+    let mut bus = Bus::with_rom(Rom::new(&ines(1, 1, 0, 0x08)).unwrap()).unwrap();
+    bus.mem_write(0x6000, 0x55);
+    assert_eq!(bus.mem_read(0x6000), 0);
+
+    // 2 KiB mirrors through $6000-$7FFF
+    let mut raw = ines(1, 1, 0, 0x08);
+    raw[10] = 0x05;
+    let mut bus = Bus::with_rom(Rom::new(&raw).unwrap()).unwrap();
+    bus.mem_write(0x6001, 0x55);
+    assert_eq!(bus.mem_read(0x6801), 0x55);
+    assert_eq!(bus.mem_read(0x7801), 0x55);
+    assert_eq!(bus.region, Region::Ntsc);
+}
+
+#[test]
+fn rejects_bad_files() {
+    let mut raw = ines(1, 1, 0, 0);
+    raw[3] = 0x1B;
+    assert_eq!(Rom::new(&raw).err(), Some(RomError::BadMagic));
+    assert_eq!(Rom::new(b"NES").err(), Some(RomError::BadMagic));
+
+    let raw = ines(2, 1, 0, 0);
+    assert_eq!(
+        Rom::new(&raw[..0x6000]).err(),
+        Some(RomError::Truncated {
+            expected: 16 + 0x8000 + 0x2000,
+            actual: 0x6000,
+        })
+    );
+    assert_eq!(Rom::new(&ines(0, 1, 0, 0)).err(), Some(RomError::NoPrgRom));
+}
+
+#[test]
+fn bus_rejects_unsupported_mappers() {
+    let rom = Rom::new(&ines(1, 1, 0xF0, 0xF0)).unwrap();
+    assert_eq!(
+        Bus::with_rom(rom).err(),
+        Some(RomError::UnsupportedMapper(0xFF))
+    );
+}
+
+#[test]
+fn bus_maps_prg_rom_at_8000() {
+    // one 16 KiB bank shows up at $8000 and again at $C000
+    let mut bus = Bus::with_rom(Rom::new(&ines(1, 1, 0, 0)).unwrap()).unwrap();
+    assert_eq!(bus.mem_read(0x8000), 0);
+    assert_eq!(bus.mem_read(0xFFFF), 0);
+
+    let mut bus = Bus::with_rom(Rom::new(&ines(2, 1, 0, 0)).unwrap()).unwrap();
+    assert_eq!(bus.mem_read(0xBFFF), 0);
+    assert_eq!(bus.mem_read(0xC000), 1);
+
+    // ROM ignores writes; PRG RAM below it does not
+    bus.mem_write(0xC000, 0x55);
+    assert_eq!(bus.mem_read(0xC000), 1);
+    bus.mem_write(0x6000, 0x55);
+    assert_eq!(bus.mem_read(0x6000), 0x55);
+
+    let mapper = bus.mapper_mut().unwrap();
+    assert_eq!(mapper.ppu_read(0x0000), 0xC7);
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+}
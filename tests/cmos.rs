@@ -0,0 +1,88 @@
+mod common;
+
+use common::run;
+use nes_emulator::constants::{CpuError, StatusFlag};
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+#[test]
+fn stz_and_bra() {
+    // LDA #$55; STA $10; STZ $10; BRA +2; LDA #$01; LDA #$01 (skipped); BRK
+    let cpu = run(
+        CpuVariant::Cmos65C02,
+        &[0xa9, 0x55, 0x85, 0x10, 0x64, 0x10, 0x80, 0x02, 0xa9, 0x01],
+    );
+    assert_eq!(cpu.mem_peek(0x10), 0x00);
+    assert_eq!(cpu.reg_a, 0x55);
+}
+
+#[test]
+fn push_and_pull_index_registers() {
+    // LDX #$12; PHX; PLY
+    let cpu = run(CpuVariant::Cmos65C02, &[0xa2, 0x12, 0xda, 0x7a]);
+    assert_eq!(cpu.reg_y, 0x12);
+}
+
+#[test]
+fn tsb_and_trb() {
+    // LDA #$0F; STA $10; LDA #$F0; TSB $10; LDA #$0C; TRB $10
+    let cpu = run(
+        CpuVariant::Cmos65C02,
+        &[0xa9, 0x0f, 0x85, 0x10, 0xa9, 0xf0, 0x04, 0x10, 0xa9, 0x0c, 0x14, 0x10],
+    );
+    assert_eq!(cpu.mem_peek(0x10), 0xF3);
+    // A & $FF before TRB was non-zero
+    assert!(!cpu.check_flag(StatusFlag::Zero));
+}
+
+#[test]
+fn inc_a_and_bit_immediate() {
+    // LDA #$FF; INC A; LDA #$C0; BIT #$01
+    let cpu = run(CpuVariant::Cmos65C02, &[0xa9, 0xff, 0x1a, 0xa9, 0xc0, 0x89, 0x01]);
+    assert_eq!(cpu.reg_a, 0xC0);
+    // N from LDA, untouched by BIT #imm
+    assert!(cpu.check_flag(StatusFlag::Negative));
+    assert!(cpu.check_flag(StatusFlag::Zero));
+}
+
+#[test]
+fn zero_page_indirect() {
+    // LDA #$34; STA $20; LDA #$12; STA $21; LDA #$99; STA ($20); LDA #$00; LDA ($20)
+    let cpu = run(
+        CpuVariant::Cmos65C02,
+        &[
+            0xa9, 0x34, 0x85, 0x20, 0xa9, 0x12, 0x85, 0x21, 0xa9, 0x99, 0x92, 0x20, 0xa9, 0x00,
+            0xb2, 0x20,
+        ],
+    );
+    assert_eq!(cpu.mem_peek(0x1234), 0x99);
+    assert_eq!(cpu.reg_a, 0x99);
+}
+
+#[test]
+fn decimal_result_sets_zero_flag() {
+    // SED; CLC; LDA #$99; ADC #$01
+    let cpu = run(CpuVariant::Cmos65C02, &[0xf8, 0x18, 0xa9, 0x99, 0x69, 0x01]);
+    assert_eq!(cpu.reg_a, 0x00);
+    assert!(cpu.check_flag(StatusFlag::Zero));
+    assert!(!cpu.check_flag(StatusFlag::Negative));
+    assert!(cpu.check_flag(StatusFlag::Carry));
+}
+
+#[test]
+fn unofficial_opcodes_are_nops() {
+    // $A7 (*LAX zp on NMOS) is a one-byte NOP, so $EA runs as the next opcode
+    let cpu = run(CpuVariant::Cmos65C02, &[0xa7, 0xea, 0xa9, 0x07]);
+    assert_eq!(cpu.reg_a, 0x07);
+    assert_eq!(cpu.reg_x, 0x00);
+
+    let mut nmos = CPU::new(FlatMemory::new());
+    nmos.load(vec![0x02]);
+    nmos.reset();
+    assert!(matches!(nmos.step(), Err(CpuError::Jam { .. })));
+}
+
+#[test]
+fn nes_mode_is_the_default() {
+    assert_eq!(CPU::new(FlatMemory::new()).variant(), CpuVariant::Ricoh2A03);
+}
@@ -0,0 +1,25 @@
+// Helpers shared by the integration tests that run small programs.
+
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+// generous for the short programs the tests load, small enough that a
+// runaway loop fails fast
+pub const CYCLE_LIMIT: u64 = 100_000;
+
+// Powers on a CPU of the given variant, loads the program at $0600 and runs
+// it up to the first BRK, which the zeroed memory after it supplies.
+pub fn run(variant: CpuVariant, program: &[u8]) -> CPU<FlatMemory> {
+    let mut cpu = CPU::with_variant(FlatMemory::new(), variant);
+    cpu.power_on();
+    cpu.load(program.to_vec());
+    while cpu.mem_peek(cpu.program_counter) != 0x00 {
+        cpu.step().expect("program faulted");
+        assert!(
+            cpu.cycles < CYCLE_LIMIT,
+            "program did not reach a BRK within {} cycles",
+            CYCLE_LIMIT
+        );
+    }
+    cpu
+}
@@ -0,0 +1,224 @@
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read(u16),
+    Write(u16, u8),
+}
+
+// flat memory that logs every bus access
+struct Recorder {
+    memory: FlatMemory,
+    log: Vec<Access>,
+}
+
+impl Mem for Recorder {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.log.push(Access::Read(addr));
+        self.memory.mem_read(addr)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.memory.mem_peek(addr)
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.log.push(Access::Write(addr, data));
+        self.memory.mem_write(addr, data);
+    }
+}
+
+fn recorder_cpu(program: &[u8]) -> CPU<Recorder> {
+    let mut cpu = CPU::new(Recorder {
+        memory: FlatMemory::new(),
+        log: Vec::new(),
+    });
+    for (i, byte) in program.iter().enumerate() {
+        cpu.mem_write(0x0200 + i as u16, *byte);
+    }
+    cpu.program_counter = 0x0200;
+    cpu.cycle_stepped = true;
+    cpu.bus.log.clear();
+    cpu
+}
+
+#[test]
+fn read_modify_write_writes_twice() {
+    // INC $1234, in both stepping modes
+    for cycle_stepped in [false, true] {
+        let mut cpu = recorder_cpu(&[0xee, 0x34, 0x12]);
+        cpu.cycle_stepped = cycle_stepped;
+        cpu.mem_write(0x1234, 0x41);
+        cpu.bus.log.clear();
+        let info = cpu.step().unwrap();
+
+        assert_eq!(info.cycles, 6);
+        assert_eq!(
+            cpu.bus.log,
+            vec![
+                Access::Read(0x0200),
+                Access::Read(0x0201),
+                Access::Read(0x0202),
+                Access::Read(0x1234),
+                Access::Write(0x1234, 0x41),
+                Access::Write(0x1234, 0x42),
+            ],
+            "cycle_stepped = {}",
+            cycle_stepped
+        );
+    }
+}
+
+#[test]
+fn indexed_read_across_page_reads_unfixed_address_first() {
+    // LDX #$10; LDA $12F8,X
+    let mut cpu = recorder_cpu(&[0xa2, 0x10, 0xbd, 0xf8, 0x12]);
+    cpu.step().unwrap();
+    cpu.bus.log.clear();
+    let info = cpu.step().unwrap();
+
+    assert_eq!(info.cycles, 5);
+    assert_eq!(
+        &cpu.bus.log[3..],
+        &[Access::Read(0x1208), Access::Read(0x1308)]
+    );
+}
+
+#[test]
+fn indexed_store_always_does_a_dummy_read() {
+    // LDX #$01; STA $1200,X
+    let mut cpu = recorder_cpu(&[0xa2, 0x01, 0x9d, 0x00, 0x12]);
+    cpu.step().unwrap();
+    cpu.bus.log.clear();
+    let info = cpu.step().unwrap();
+
+    assert_eq!(info.cycles, 5);
+    assert_eq!(
+        &cpu.bus.log[3..],
+        &[Access::Read(0x1201), Access::Write(0x1201, 0x00)]
+    );
+}
+
+#[test]
+fn tick_advances_one_cycle_per_call() {
+    // LDA $10; NOP
+    let mut cpu = recorder_cpu(&[0xa5, 0x10, 0xea]);
+    assert_eq!(cpu.tick().unwrap().map(|info| info.cycles), None);
+    assert_eq!(cpu.cycles, 1);
+    let info = cpu.tick().unwrap();
+    assert!(info.is_none());
+    let info = cpu.tick().unwrap().expect("LDA zero page takes 3 cycles");
+    assert_eq!(info.counter, 0x0200);
+    assert_eq!(cpu.bus.log.len(), 3);
+    assert_eq!(cpu.program_counter, 0x0202);
+}
+
+fn seeded_memory() -> FlatMemory {
+    let mut memory = FlatMemory::new();
+    let mut seed: u32 = 0x1234_5678;
+    for addr in 0..=0xFFFF_u16 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        memory.mem_write(addr, (seed >> 16) as u8);
+    }
+    memory
+}
+
+// Every opcode must leave the same machine state and take the same number of
+// cycles whether it runs atomically or one tick at a time.
+#[test]
+fn tick_matches_step_for_every_opcode() {
+    for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+        for status in [0x20, 0xEF] {
+            for opcode in 0..=0xFF_u8 {
+                let mut cpus: Vec<CPU<FlatMemory>> = (0..2)
+                    .map(|_| {
+                        let mut cpu = CPU::with_variant(seeded_memory(), variant);
+                        cpu.mem_write(0x0300, opcode);
+                        cpu.program_counter = 0x0300;
+                        cpu.reg_a = 0x5A;
+                        cpu.reg_x = 0x85;
+                        cpu.reg_y = 0xF3;
+                        cpu.stp = 0x80;
+                        cpu.status = status;
+                        cpu
+                    })
+                    .collect();
+                cpus[1].cycle_stepped = true;
+
+                let atomic = cpus[0].step().map(|info| info.cycles);
+                let stepped = cpus[1].step().map(|info| info.cycles);
+                let context = format!(
+                    "{:?} opcode {:#04x} status {:#04x}",
+                    variant, opcode, status
+                );
+                match (atomic, stepped) {
+                    (Ok(a), Ok(b)) => assert_eq!(a, b, "cycles for {}", context),
+                    (Err(_), Err(_)) => continue,
+                    (a, b) => panic!("{}: step {:?}, tick {:?}", context, a, b),
+                }
+
+                let (a, b) = (&cpus[0], &cpus[1]);
+                assert_eq!(
+                    (
+                        a.reg_a,
+                        a.reg_x,
+                        a.reg_y,
+                        a.status,
+                        a.stp,
+                        a.program_counter
+                    ),
+                    (
+                        b.reg_a,
+                        b.reg_x,
+                        b.reg_y,
+                        b.status,
+                        b.stp,
+                        b.program_counter
+                    ),
+                    "registers for {}",
+                    context
+                );
+                for addr in 0..=0xFFFF_u16 {
+                    assert_eq!(
+                        a.mem_peek(addr),
+                        b.mem_peek(addr),
+                        "memory at {:#06x} for {}",
+                        addr,
+                        context
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn nmi_sequence_bus_accesses() {
+    // NOP, with the NMI handler at $0300
+    let mut cpu = recorder_cpu(&[0xea]);
+    cpu.mem_write_u16(0xfffa, 0x0300);
+    cpu.mem_write(0x0300, 0xea);
+    cpu.stp = 0xfd;
+    cpu.status = 0x24;
+    cpu.bus.log.clear();
+    cpu.trigger_nmi();
+    for _ in 0..7 {
+        cpu.tick().unwrap();
+    }
+
+    // two reads of the interrupted opcode, three pushes, the vector
+    assert_eq!(
+        cpu.bus.log,
+        vec![
+            Access::Read(0x0200),
+            Access::Read(0x0200),
+            Access::Write(0x01fd, 0x02),
+            Access::Write(0x01fc, 0x00),
+            Access::Write(0x01fb, 0x24),
+            Access::Read(0xfffa),
+            Access::Read(0xfffb),
+        ]
+    );
+    assert_eq!(cpu.program_counter, 0x0300);
+}
@@ -0,0 +1,108 @@
+// Base cycle counts plus the extra cycles for indexed reads that cross a
+// page and for taken branches.
+
+use nes_emulator::constants::StatusFlag;
+use nes_emulator::cpu::CPU;
+use nes_emulator::mem::{FlatMemory, Mem};
+
+// Cycles the instruction at `pc` takes, checked to be the same in atomic
+// and cycle-stepped mode.
+fn cycles(pc: u16, program: &[u8], setup: impl Fn(&mut CPU<FlatMemory>)) -> u64 {
+    let counts: Vec<u64> = [false, true]
+        .iter()
+        .map(|&cycle_stepped| {
+            let mut cpu = CPU::new(FlatMemory::new());
+            for (i, byte) in program.iter().enumerate() {
+                cpu.mem_write(pc.wrapping_add(i as u16), *byte);
+            }
+            cpu.program_counter = pc;
+            cpu.cycle_stepped = cycle_stepped;
+            setup(&mut cpu);
+            let info = cpu.step().expect("instruction faulted");
+            assert_eq!(info.cycles, cpu.cycles);
+            info.cycles
+        })
+        .collect();
+    assert_eq!(counts[0], counts[1], "atomic and cycle-stepped disagree");
+    counts[0]
+}
+
+#[test]
+fn indexed_reads_pay_for_a_page_cross() {
+    // (opcode, base cycles): LDA abs,X / abs,Y, LAX abs,Y, NOP abs,X
+    for (opcode, base) in [(0xbd, 4), (0xb9, 4), (0xbf, 4), (0x1c, 4)] {
+        let same_page = cycles(0x0200, &[opcode, 0x10, 0x12], |cpu| {
+            cpu.reg_x = 0x20;
+            cpu.reg_y = 0x20;
+        });
+        let crossed = cycles(0x0200, &[opcode, 0xf0, 0x12], |cpu| {
+            cpu.reg_x = 0x20;
+            cpu.reg_y = 0x20;
+        });
+        assert_eq!(
+            (same_page, crossed),
+            (base, base + 1),
+            "opcode {:#04x}",
+            opcode
+        );
+    }
+}
+
+#[test]
+fn indirect_indexed_read_pays_for_a_page_cross() {
+    // LDA ($10),Y with the pointer at $10 holding $12F0 or $1210
+    for (low, expected) in [(0x10, 5), (0xf0, 6)] {
+        let count = cycles(0x0200, &[0xb1, 0x10], |cpu| {
+            cpu.mem_write(0x10, low);
+            cpu.mem_write(0x11, 0x12);
+            cpu.reg_y = 0x20;
+        });
+        assert_eq!(count, expected, "pointer low byte {:#04x}", low);
+    }
+}
+
+#[test]
+fn stores_and_read_modify_write_never_pay_for_a_page_cross() {
+    // (opcode, cycles): STA abs,X / abs,Y / (zp),Y, INC abs,X, SLO abs,Y, DCP (zp),Y
+    let cases = [
+        (0x9d, 5),
+        (0x99, 5),
+        (0x91, 6),
+        (0xfe, 7),
+        (0x1b, 7),
+        (0xd3, 8),
+    ];
+    for (opcode, expected) in cases {
+        for low in [0x10, 0xf0] {
+            let count = cycles(0x0200, &[opcode, low, 0x12], |cpu| {
+                // the (zp),Y forms use $10 or $F0 as the pointer address
+                cpu.mem_write(low as u16, low);
+                cpu.mem_write(low as u16 + 1, 0x12);
+                cpu.reg_x = 0x20;
+                cpu.reg_y = 0x20;
+            });
+            assert_eq!(count, expected, "opcode {:#04x} base {:#04x}", opcode, low);
+        }
+    }
+}
+
+#[test]
+fn branches_take_two_three_or_four_cycles() {
+    // BNE +$10 with Z set: not taken
+    let not_taken = cycles(0x0200, &[0xd0, 0x10], |cpu| {
+        cpu.status |= StatusFlag::Zero as u8
+    });
+    assert_eq!(not_taken, 2);
+
+    // BNE +$10 from $0202 lands on $0212
+    let taken = cycles(0x0200, &[0xd0, 0x10], |_| {});
+    assert_eq!(taken, 3);
+
+    // BNE +$10 from $02F2 lands on $0302
+    let crossed = cycles(0x02f0, &[0xd0, 0x10], |_| {});
+    assert_eq!(crossed, 4);
+
+    // BNE -$10 from $0302 lands back on $02F2
+    let backwards = cycles(0x0300, &[0xd0, 0xf0], |_| {});
+    assert_eq!(backwards, 4);
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::run;
+use nes_emulator::constants::StatusFlag;
+use nes_emulator::cpu::CpuVariant;
+
+#[test]
+fn nmos_adc_adds_bcd() {
+    // SED; CLC; LDA #$09; ADC #$01
+    let cpu = run(CpuVariant::Nmos6502, &[0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01]);
+    assert_eq!(cpu.reg_a, 0x10);
+    assert!(!cpu.check_flag(StatusFlag::Carry));
+}
+
+#[test]
+fn nmos_adc_carries_out_of_99() {
+    // SED; CLC; LDA #$99; ADC #$01
+    let cpu = run(CpuVariant::Nmos6502, &[0xf8, 0x18, 0xa9, 0x99, 0x69, 0x01]);
+    assert_eq!(cpu.reg_a, 0x00);
+    assert!(cpu.check_flag(StatusFlag::Carry));
+    // Z follows the binary sum ($9A) and N the half-adjusted one ($A0)
+    assert!(!cpu.check_flag(StatusFlag::Zero));
+    assert!(cpu.check_flag(StatusFlag::Negative));
+}
+
+#[test]
+fn nmos_sbc_borrows_bcd() {
+    // SED; SEC; LDA #$00; SBC #$01
+    let cpu = run(CpuVariant::Nmos6502, &[0xf8, 0x38, 0xa9, 0x00, 0xe9, 0x01]);
+    assert_eq!(cpu.reg_a, 0x99);
+    assert!(!cpu.check_flag(StatusFlag::Carry));
+}
+
+#[test]
+fn ricoh_2a03_ignores_decimal_flag() {
+    // SED; CLC; LDA #$09; ADC #$01
+    let cpu = run(CpuVariant::Ricoh2A03, &[0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01]);
+    assert_eq!(cpu.reg_a, 0x0A);
+    assert!(cpu.check_flag(StatusFlag::DecimalMode));
+}
@@ -0,0 +1,132 @@
+use nes_emulator::constants::AddressingMode;
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::disasm::{decode, disassemble, disassemble_at, disassemble_range};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+fn operands(variant: CpuVariant, bytes: &[u8]) -> Vec<String> {
+    disassemble(variant, 0x0600, bytes)
+        .iter()
+        .map(|i| format!("{} {}", i.mnemonic, i.operand).trim_end().to_string())
+        .collect()
+}
+
+#[test]
+fn formats_every_addressing_mode() {
+    let program = [
+        0xea, // NOP
+        0x0a, // ASL A
+        0xa9, 0x12, // LDA #$12
+        0xa5, 0x12, // LDA $12
+        0xb5, 0x12, // LDA $12,X
+        0xb6, 0x12, // LDX $12,Y
+        0xad, 0x34, 0x12, // LDA $1234
+        0xbd, 0x34, 0x12, // LDA $1234,X
+        0xb9, 0x34, 0x12, // LDA $1234,Y
+        0x6c, 0x34, 0x12, // JMP ($1234)
+        0xa1, 0x34, // LDA ($34,X)
+        0xb1, 0x34, // LDA ($34),Y
+    ];
+    assert_eq!(
+        operands(CpuVariant::Ricoh2A03, &program),
+        [
+            "NOP",
+            "ASL A",
+            "LDA #$12",
+            "LDA $12",
+            "LDA $12,X",
+            "LDX $12,Y",
+            "LDA $1234",
+            "LDA $1234,X",
+            "LDA $1234,Y",
+            "JMP ($1234)",
+            "LDA ($34,X)",
+            "LDA ($34),Y",
+        ]
+    );
+}
+
+#[test]
+fn formats_cmos_modes() {
+    // LDA ($34); JMP ($1234,X)
+    assert_eq!(
+        operands(CpuVariant::Cmos65C02, &[0xb2, 0x34, 0x7c, 0x34, 0x12]),
+        ["LDA ($34)", "JMP ($1234,X)"]
+    );
+}
+
+#[test]
+fn branches_show_their_target() {
+    // BNE +2 at $0600, BEQ -2 at $0602
+    let lines = disassemble(CpuVariant::Ricoh2A03, 0x0600, &[0xd0, 0x02, 0xf0, 0xfe]);
+    assert_eq!(lines[0].operand, "$0604");
+    assert_eq!(lines[1].operand, "$0602");
+    assert_eq!(lines[1].mode, AddressingMode::Relative);
+}
+
+#[test]
+fn truncated_instruction_becomes_data() {
+    // LDA $1234 missing its high byte
+    let lines = disassemble(CpuVariant::Ricoh2A03, 0xfffe, &[0xad, 0x34]);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].mnemonic, ".byte");
+    assert_eq!(lines[0].to_string(), "FFFE  AD       .byte $AD");
+    assert_eq!(lines[1].addr, 0xffff);
+}
+
+#[test]
+fn displays_like_a_listing() {
+    let instruction = decode(CpuVariant::Ricoh2A03, 0xc000, &[0x4c, 0xf5, 0xc5]);
+    assert_eq!(instruction.to_string(), "C000  4C F5 C5   JMP $C5F5");
+    assert_eq!(instruction.next_addr(), 0xc003);
+}
+
+#[test]
+fn range_reads_memory_without_side_effects() {
+    let mut mem = FlatMemory::new();
+    for (i, byte) in [0xa2, 0x05, 0xca, 0xd0, 0xfd].iter().enumerate() {
+        mem.mem_write(0x8000 + i as u16, *byte);
+    }
+    let lines = disassemble_range(CpuVariant::Ricoh2A03, &mem, 0x8000..=0x8004);
+    let text: Vec<String> = lines.iter().map(|i| i.to_string()).collect();
+    assert_eq!(
+        text,
+        [
+            "8000  A2 05      LDX #$05",
+            "8002  CA         DEX",
+            "8003  D0 FD      BNE $8002",
+        ]
+    );
+}
+
+#[test]
+fn effective_address_uses_cpu_state() {
+    let mut cpu = CPU::new(FlatMemory::new());
+    cpu.reg_x = 0x04;
+    cpu.reg_y = 0x10;
+    cpu.mem_write_u16(0x0038, 0x2000);
+    cpu.mem_write_u16(0x0034, 0x3000);
+    // LDA ($34,X); LDA ($34),Y; LDA $12FF,X; LDA #$01
+    for (i, byte) in [0xa1, 0x34, 0xb1, 0x34, 0xbd, 0xff, 0x12, 0xa9, 0x01]
+        .iter()
+        .enumerate()
+    {
+        cpu.mem_write(0x0600 + i as u16, *byte);
+    }
+    assert_eq!(disassemble_at(&cpu, 0x0600).effective_addr, Some(0x2000));
+    assert_eq!(disassemble_at(&cpu, 0x0602).effective_addr, Some(0x3010));
+    assert_eq!(disassemble_at(&cpu, 0x0604).effective_addr, Some(0x1303));
+    assert_eq!(disassemble_at(&cpu, 0x0607).effective_addr, None);
+}
+
+#[test]
+fn indirect_jump_follows_the_page_wrap() {
+    let mut cpu = CPU::new(FlatMemory::new());
+    // JMP ($02FF) reads its high byte from $0200 on NMOS
+    for (i, byte) in [0x6c, 0xff, 0x02].iter().enumerate() {
+        cpu.mem_write(0x0600 + i as u16, *byte);
+    }
+    cpu.mem_write(0x02ff, 0x34);
+    cpu.mem_write(0x0200, 0x12);
+    cpu.mem_write(0x0300, 0x56);
+    assert_eq!(disassemble_at(&cpu, 0x0600).effective_addr, Some(0x1234));
+}
@@ -0,0 +1,97 @@
+// Runs Klaus Dormann's 6502 functional and decimal test images on flat
+// memory. Each test ends in a trap (a branch or jump to itself); where it
+// traps tells whether it passed. The images live in tests/fixtures and are
+// not shipped, so the tests are ignored by default; run them with
+// `cargo test --release --test dormann -- --ignored`.
+
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+use std::fs;
+use std::path::PathBuf;
+
+// more than the functional test needs (about 30 million instructions)
+const MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn load_image(image: &[u8], load_addr: u16, entry: u16) -> CPU<FlatMemory> {
+    assert!(
+        load_addr as usize + image.len() <= 0x10000,
+        "image does not fit at {:#06x}",
+        load_addr
+    );
+    // both images check decimal mode, which the 2A03 does not have
+    let mut cpu = CPU::with_variant(FlatMemory::new(), CpuVariant::Nmos6502);
+    for (i, byte) in image.iter().enumerate() {
+        cpu.mem_write(load_addr + i as u16, *byte);
+    }
+    cpu.program_counter = entry;
+    cpu
+}
+
+// Steps until an instruction leaves PC where it was and returns the trap address.
+fn run_to_trap(cpu: &mut CPU<FlatMemory>) -> u16 {
+    for _ in 0..MAX_INSTRUCTIONS {
+        let counter = cpu.program_counter;
+        if let Err(e) = cpu.step() {
+            panic!("cpu error: {}", e);
+        }
+        if cpu.program_counter == counter {
+            return counter;
+        }
+    }
+    panic!(
+        "no trap after {} instructions, PC at {:#06x}",
+        MAX_INSTRUCTIONS, cpu.program_counter
+    );
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/6502_functional_test.bin"]
+fn functional_test() {
+    // 6502_functional_test.bin as assembled by default: a full 64 KiB image
+    // with code at $0400 and the success trap at $3469
+    const ENTRY: u16 = 0x0400;
+    const SUCCESS: u16 = 0x3469;
+    const TEST_CASE: u16 = 0x0200;
+
+    let image = fixture("6502_functional_test.bin");
+    let mut cpu = load_image(&image, 0x0000, ENTRY);
+    let trap = run_to_trap(&mut cpu);
+    assert_eq!(
+        trap,
+        SUCCESS,
+        "trapped at {:#06x} in test case {:#04x}",
+        trap,
+        cpu.mem_read(TEST_CASE)
+    );
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/6502_decimal_test.bin"]
+fn decimal_test() {
+    // 6502_decimal_test.bin assembled with its default org of $0200; it
+    // traps at DONE and leaves ERROR non-zero if any result was wrong
+    const ORG: u16 = 0x0200;
+    const ERROR: u16 = 0x000B;
+    const N1: u16 = 0x0000;
+    const N2: u16 = 0x0001;
+
+    let image = fixture("6502_decimal_test.bin");
+    let mut cpu = load_image(&image, ORG, ORG);
+    let trap = run_to_trap(&mut cpu);
+    assert_eq!(
+        cpu.mem_read(ERROR),
+        0,
+        "trapped at {:#06x} after failing on N1={:#04x} N2={:#04x}",
+        trap,
+        cpu.mem_read(N1),
+        cpu.mem_read(N2)
+    );
+}
@@ -0,0 +1,176 @@
+// NMI is edge-triggered, IRQ is level-triggered and masked by I, and only
+// BRK and PHP push the status with B set.
+
+use nes_emulator::constants::{InterruptType, StatusFlag};
+use nes_emulator::cpu::CPU;
+use nes_emulator::mem::{FlatMemory, Mem};
+
+const B: u8 = StatusFlag::Break as u8;
+const U: u8 = StatusFlag::Unused as u8;
+const I: u8 = StatusFlag::InterruptDisable as u8;
+
+// NOPs at $0200, the NMI handler at $0300 and the IRQ/BRK handler at $0400,
+// with I clear so IRQs get through.
+fn cpu_with_handlers(nmi_handler: &[u8], irq_handler: &[u8]) -> CPU<FlatMemory> {
+    let mut cpu = CPU::new(FlatMemory::new());
+    for addr in 0x0200..0x0280 {
+        cpu.mem_write(addr, 0xea);
+    }
+    for (i, byte) in nmi_handler.iter().enumerate() {
+        cpu.mem_write(0x0300 + i as u16, *byte);
+    }
+    for (i, byte) in irq_handler.iter().enumerate() {
+        cpu.mem_write(0x0400 + i as u16, *byte);
+    }
+    cpu.mem_write_u16(0xfffa, 0x0300);
+    cpu.mem_write_u16(0xfffe, 0x0400);
+    cpu.program_counter = 0x0200;
+    cpu.status = U;
+    cpu
+}
+
+// the byte the last push left on the stack
+fn pushed(cpu: &CPU<FlatMemory>) -> u8 {
+    cpu.mem_peek(0x0100 + cpu.stp.wrapping_add(1) as u16)
+}
+
+#[test]
+fn nmi_fires_once_per_edge() {
+    // RTI right away, so the handler returns to the NOPs
+    let mut cpu = cpu_with_handlers(&[0x40], &[]);
+    cpu.set_nmi_line(true);
+    let info = cpu.step().unwrap();
+    assert_eq!(info.interrupt, Some(InterruptType::NMI));
+    assert_eq!(info.counter, 0x0300);
+
+    // the line stays asserted, but there is no new edge
+    for _ in 0..3 {
+        assert_eq!(cpu.step().unwrap().interrupt, None);
+    }
+
+    cpu.set_nmi_line(false);
+    assert_eq!(cpu.step().unwrap().interrupt, None);
+    cpu.set_nmi_line(true);
+    assert_eq!(cpu.step().unwrap().interrupt, Some(InterruptType::NMI));
+}
+
+#[test]
+fn nmi_is_taken_even_with_i_set() {
+    let mut cpu = cpu_with_handlers(&[0xea], &[]);
+    cpu.status |= I;
+    cpu.trigger_nmi();
+    assert_eq!(cpu.step().unwrap().interrupt, Some(InterruptType::NMI));
+}
+
+#[test]
+fn irq_is_level_triggered_and_masked_by_i() {
+    // CLI; NOP: the handler unmasks right away, so a held line retriggers
+    let mut cpu = cpu_with_handlers(&[], &[0x58, 0xea]);
+    cpu.set_irq_line(true);
+    let info = cpu.step().unwrap();
+    assert_eq!(info.interrupt, Some(InterruptType::IRQ));
+    assert_eq!(info.counter, 0x0400);
+    assert_eq!(cpu.step().unwrap().interrupt, Some(InterruptType::IRQ));
+
+    cpu.set_irq_line(false);
+    assert_eq!(cpu.step().unwrap().interrupt, None);
+
+    // with I set the line is ignored for as long as it is held
+    let mut cpu = cpu_with_handlers(&[], &[]);
+    cpu.status |= I;
+    cpu.set_irq_line(true);
+    for _ in 0..3 {
+        assert_eq!(cpu.step().unwrap().interrupt, None);
+    }
+    assert_eq!(cpu.program_counter, 0x0203);
+}
+
+#[test]
+fn b_is_set_only_in_the_status_brk_and_php_push() {
+    // BRK
+    let mut cpu = cpu_with_handlers(&[], &[0xea]);
+    cpu.mem_write(0x0200, 0x00);
+    cpu.step().unwrap();
+    assert_eq!(pushed(&cpu), B | U);
+    assert_eq!(cpu.status & B, 0);
+
+    // PHP
+    let mut cpu = cpu_with_handlers(&[], &[]);
+    cpu.mem_write(0x0200, 0x08);
+    cpu.step().unwrap();
+    assert_eq!(pushed(&cpu), B | U);
+    assert_eq!(cpu.status & B, 0);
+
+    // NMI
+    let mut cpu = cpu_with_handlers(&[0xea], &[]);
+    cpu.trigger_nmi();
+    cpu.step().unwrap();
+    assert_eq!(pushed(&cpu), U);
+
+    // IRQ
+    let mut cpu = cpu_with_handlers(&[], &[0xea]);
+    cpu.set_irq_line(true);
+    cpu.step().unwrap();
+    assert_eq!(pushed(&cpu), U);
+    assert_eq!(cpu.status & B, 0);
+}
+
+#[test]
+fn rti_and_plp_ignore_bits_4_and_5() {
+    for (pulled, status) in [
+        (0x00, U),
+        (B, U),
+        (0xff, !B),
+        (B | U | 0x81, U | 0x81),
+    ] {
+        // PLP
+        let mut cpu = cpu_with_handlers(&[], &[]);
+        cpu.mem_write(0x0200, 0x28);
+        cpu.stp = 0xfc;
+        cpu.mem_write(0x01fd, pulled);
+        cpu.step().unwrap();
+        assert_eq!(cpu.status, status, "PLP of {:#04x}", pulled);
+
+        // RTI to $0234
+        let mut cpu = cpu_with_handlers(&[], &[]);
+        cpu.mem_write(0x0200, 0x40);
+        cpu.stp = 0xfc;
+        cpu.mem_write(0x01fd, pulled);
+        cpu.mem_write_u16(0x01fe, 0x0234);
+        cpu.step().unwrap();
+        assert_eq!(cpu.status, status, "RTI of {:#04x}", pulled);
+        assert_eq!(cpu.program_counter, 0x0234);
+    }
+}
+
+#[test]
+fn interrupt_sequence_takes_seven_cycles() {
+    for cycle_stepped in [false, true] {
+        // the step runs the sequence and the handler's first NOP
+        let mut cpu = cpu_with_handlers(&[0xea], &[0xea]);
+        cpu.cycle_stepped = cycle_stepped;
+        cpu.trigger_nmi();
+        let info = cpu.step().unwrap();
+        assert_eq!(info.cycles, 7 + 2, "cycle_stepped = {}", cycle_stepped);
+        assert_eq!(cpu.stp, 0xfa);
+        assert_eq!(cpu.mem_peek(0x01fd), 0x02);
+        assert_eq!(cpu.mem_peek(0x01fc), 0x00);
+
+        let mut cpu = cpu_with_handlers(&[0xea], &[0xea]);
+        cpu.cycle_stepped = cycle_stepped;
+        cpu.set_irq_line(true);
+        let info = cpu.step().unwrap();
+        assert_eq!(info.cycles, 7 + 2, "cycle_stepped = {}", cycle_stepped);
+    }
+
+    // tick by tick, the handler's opcode is fetched on the eighth cycle
+    let mut cpu = cpu_with_handlers(&[0xea], &[]);
+    cpu.cycle_stepped = true;
+    cpu.trigger_nmi();
+    for _ in 0..7 {
+        assert!(cpu.tick().unwrap().is_none());
+    }
+    assert_eq!(cpu.program_counter, 0x0300);
+    assert!(cpu.tick().unwrap().is_none());
+    assert_eq!(cpu.tick().unwrap().map(|info| info.counter), Some(0x0300));
+}
@@ -0,0 +1,205 @@
+use nes_emulator::cpu::CPU;
+use nes_emulator::loader::{
+    Image, ImageFormat, LoadError, LoadOptions, Segment, load_file, load_image, parse_image,
+};
+use nes_emulator::mem::{FlatMemory, Mem};
+use std::path::{Path, PathBuf};
+
+const INTEL_HEX: &str = "\
+:04060000A942851076
+:0106040000F5
+:020000040000FA
+:02FFFC000006FD
+:0400000500000600F1
+:00000001FF
+";
+
+const SRECORD: &str = "\
+S0060000686472BB
+S1058000EAEAA6
+S20700C0004C00806C
+S5030002FA
+S90380007C
+";
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("nes_emulator_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn format_follows_the_extension() {
+    assert_eq!(
+        ImageFormat::from_path(Path::new("a.HEX")),
+        ImageFormat::IntelHex
+    );
+    assert_eq!(
+        ImageFormat::from_path(Path::new("a.s19")),
+        ImageFormat::SRecord
+    );
+    assert_eq!(
+        ImageFormat::from_path(Path::new("a.bin")),
+        ImageFormat::Binary
+    );
+    assert_eq!(
+        ImageFormat::from_path(Path::new("prog")),
+        ImageFormat::Binary
+    );
+}
+
+#[test]
+fn parses_intel_hex() {
+    let image = parse_image(ImageFormat::IntelHex, INTEL_HEX.as_bytes(), 0).unwrap();
+    assert_eq!(
+        image,
+        Image {
+            segments: vec![
+                Segment {
+                    addr: 0x0600,
+                    bytes: vec![0xa9, 0x42, 0x85, 0x10, 0x00],
+                },
+                Segment {
+                    addr: 0xfffc,
+                    bytes: vec![0x00, 0x06],
+                },
+            ],
+            entry: Some(0x0600),
+        }
+    );
+}
+
+#[test]
+fn parses_srecords() {
+    let image = parse_image(ImageFormat::SRecord, SRECORD.as_bytes(), 0).unwrap();
+    assert_eq!(
+        image.segments,
+        [
+            Segment {
+                addr: 0x8000,
+                bytes: vec![0xea, 0xea],
+            },
+            Segment {
+                addr: 0xc000,
+                bytes: vec![0x4c, 0x00, 0x80],
+            },
+        ]
+    );
+    assert_eq!(image.entry, Some(0x8000));
+}
+
+#[test]
+fn bad_records_report_their_line() {
+    let corrupt = INTEL_HEX.replace(":0106040000F5", ":0106040000F4");
+    assert!(matches!(
+        parse_image(ImageFormat::IntelHex, corrupt.as_bytes(), 0),
+        Err(LoadError::Checksum { line: 2 })
+    ));
+    assert!(matches!(
+        parse_image(ImageFormat::SRecord, b"S1058000EAEAA6\nhello\n", 0),
+        Err(LoadError::Parse { line: 2, .. })
+    ));
+    assert!(matches!(
+        parse_image(ImageFormat::SRecord, b"S1068000EAEAA6\n", 0),
+        Err(LoadError::Parse { line: 1, .. })
+    ));
+}
+
+#[test]
+fn address_records_need_their_data_length() {
+    // a type 02 segment of 4 bytes, a type 03 start of 2, a type 04 of 1
+    for record in [":04000002FFFFFFFFFE", ":020000030600F5", ":0100000401FA"] {
+        let text = format!("{record}\n:04060000A942851076\n");
+        assert!(
+            matches!(
+                parse_image(ImageFormat::IntelHex, text.as_bytes(), 0),
+                Err(LoadError::Parse { line: 1, .. })
+            ),
+            "{record}"
+        );
+    }
+}
+
+#[test]
+fn data_past_ffff_does_not_fit() {
+    let binary = vec![0xea; 0x200];
+    assert!(matches!(
+        parse_image(ImageFormat::Binary, &binary, 0xff00),
+        Err(LoadError::DoesNotFit {
+            addr: 0xff00,
+            len: 0x200
+        })
+    ));
+    // exactly up to $FFFF is fine
+    assert!(parse_image(ImageFormat::Binary, &binary, 0xfe00).is_ok());
+
+    // a HEX extended linear address of $10000
+    assert!(matches!(
+        parse_image(
+            ImageFormat::IntelHex,
+            b":020000040001F9\n:04060000A942851076\n",
+            0
+        ),
+        Err(LoadError::DoesNotFit {
+            addr: 0x10600,
+            len: 4
+        })
+    ));
+    assert!(matches!(
+        parse_image(ImageFormat::IntelHex, b":03FFFE00010203FA\n", 0),
+        Err(LoadError::DoesNotFit {
+            addr: 0xfffe,
+            len: 3
+        })
+    ));
+}
+
+#[test]
+fn loads_a_binary_and_sets_the_reset_vector() {
+    // LDA #$42; STA $10; BRK
+    let path = temp_file("prog.bin", &[0xa9, 0x42, 0x85, 0x10, 0x00]);
+    let mut cpu = CPU::new(FlatMemory::new());
+    let options = LoadOptions {
+        addr: 0x1000,
+        set_reset_vector: true,
+        ..LoadOptions::default()
+    };
+    let entry = load_file(&mut cpu, &path, &options).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(entry, 0x1000);
+    assert_eq!(cpu.mem_peek(0xfffc), 0x00);
+    assert_eq!(cpu.mem_peek(0xfffd), 0x10);
+    cpu.power_on();
+    assert_eq!(cpu.program_counter, 0x1000);
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.mem_peek(0x10), 0x42);
+}
+
+#[test]
+fn entry_override_and_pc() {
+    let image = parse_image(ImageFormat::SRecord, SRECORD.as_bytes(), 0).unwrap();
+    let mut cpu = CPU::new(FlatMemory::new());
+    let options = LoadOptions {
+        entry: Some(0xc000),
+        set_pc: true,
+        ..LoadOptions::default()
+    };
+    assert_eq!(load_image(&mut cpu, &image, &options), 0xc000);
+    assert_eq!(cpu.program_counter, 0xc000);
+    // the reset vector is left alone
+    assert_eq!(cpu.mem_peek(0xfffc), 0x00);
+    assert_eq!(cpu.mem_peek(0x8001), 0xea);
+}
+
+#[test]
+fn missing_file_is_an_io_error() {
+    let mut cpu = CPU::new(FlatMemory::new());
+    let result = load_file(
+        &mut cpu,
+        Path::new("/nonexistent/prog.hex"),
+        &LoadOptions::default(),
+    );
+    assert!(matches!(result, Err(LoadError::Io(_))));
+}
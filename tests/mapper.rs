@@ -0,0 +1,598 @@
+use nes_emulator::bus::Bus;
+use nes_emulator::cartridge::{Mirroring, Rom, RomError};
+use nes_emulator::cpu::CPU;
+use nes_emulator::mapper::{self, Board, Discrete, Mapper};
+use nes_emulator::mem::Mem;
+
+// An iNES image with the given PRG banks (16 KiB) and CHR banks (8 KiB), each
+// bank filled with its own number so reads can tell them apart.
+fn image(mapper: u8, prg_banks: u8, chr_banks: u8, flags6: u8) -> Vec<u8> {
+    let mut raw = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks];
+    raw.push(flags6 | mapper << 4);
+    raw.push(mapper & 0xF0);
+    raw.resize(16, 0);
+    for bank in 0..prg_banks {
+        raw.extend(std::iter::repeat_n(bank, 0x4000));
+    }
+    for bank in 0..chr_banks {
+        raw.extend(std::iter::repeat_n(0x80 | bank, 0x2000));
+    }
+    raw
+}
+
+fn rom(mapper: u8, prg_banks: u8, chr_banks: u8, flags6: u8) -> Rom {
+    Rom::new(&image(mapper, prg_banks, chr_banks, flags6)).unwrap()
+}
+
+// Marks the image NES 2.0 with the given submapper.
+fn nes2(raw: &mut [u8], submapper: u8) {
+    raw[7] |= 0x08;
+    raw[8] = submapper << 4;
+}
+
+// For boards with bus conflicts: $FFFF in the first 32 KiB holds $FF, so any
+// value written there reaches the latch.
+fn rom_with_ff_at_ffff(mapper: u8, prg_banks: u8, chr_banks: u8) -> Rom {
+    let mut raw = image(mapper, prg_banks, chr_banks, 0);
+    raw[16 + 0x7FFF] = 0xFF;
+    Rom::new(&raw).unwrap()
+}
+
+fn mapper_for(rom: Rom) -> Box<dyn Mapper> {
+    mapper::for_rom(rom).unwrap()
+}
+
+#[test]
+fn unknown_mappers_are_rejected() {
+    assert_eq!(
+        mapper::for_rom(rom(0xFF, 1, 1, 0)).err(),
+        Some(RomError::UnsupportedMapper(0xFF))
+    );
+}
+
+#[test]
+fn nrom_128_mirrors_its_bank() {
+    let mut nrom = mapper_for(rom(0, 1, 1, 1));
+    assert_eq!(nrom.cpu_read(0x8000), 0);
+    assert_eq!(nrom.cpu_read(0xC000), 0);
+    assert_eq!(nrom.mirroring(), Mirroring::Vertical);
+    assert!(!nrom.irq());
+}
+
+#[test]
+fn nrom_256_fills_8000_to_ffff() {
+    let mut nrom = mapper_for(rom(0, 2, 1, 0));
+    assert_eq!(nrom.cpu_read(0xBFFF), 0);
+    assert_eq!(nrom.cpu_read(0xC000), 1);
+    nrom.cpu_write(0xC000, 0x55);
+    assert_eq!(nrom.cpu_peek(0xC000), 1);
+    // nothing answers below $6000
+    assert_eq!(nrom.cpu_peek(0x5000), 0);
+}
+
+#[test]
+fn nrom_prg_ram_at_6000() {
+    let mut nrom = mapper_for(rom(0, 1, 1, 0));
+    nrom.cpu_write(0x6000, 0x12);
+    nrom.cpu_write(0x7FFF, 0x34);
+    assert_eq!(nrom.cpu_read(0x6000), 0x12);
+    assert_eq!(nrom.cpu_read(0x7FFF), 0x34);
+}
+
+#[test]
+fn nrom_chr_rom_is_read_only_and_chr_ram_is_not() {
+    let mut nrom = mapper_for(rom(0, 1, 1, 0));
+    nrom.ppu_write(0x0010, 0x55);
+    assert_eq!(nrom.ppu_read(0x0010), 0x80);
+
+    let mut nrom = mapper_for(rom(0, 1, 0, 0));
+    nrom.ppu_write(0x1FFF, 0x55);
+    assert_eq!(nrom.ppu_read(0x1FFF), 0x55);
+}
+
+// Loads an MMC1 register the way games do: five writes, low bit first, with
+// the CPU cycles of a STA in between.
+fn mmc1_write(mmc1: &mut dyn Mapper, addr: u16, value: u8) {
+    for bit in 0..5 {
+        mmc1.cpu_write(addr, value >> bit & 1);
+        for _ in 0..4 {
+            mmc1.cpu_cycle();
+        }
+    }
+}
+
+#[test]
+fn mmc1_powers_up_with_the_last_bank_fixed() {
+    let mut mmc1 = mapper_for(rom(1, 8, 1, 0));
+    assert_eq!(mmc1.cpu_read(0x8000), 0);
+    assert_eq!(mmc1.cpu_read(0xC000), 7);
+    assert_eq!(mmc1.cpu_read(0xFFFF), 7);
+}
+
+#[test]
+fn mmc1_prg_banking_modes() {
+    let mut mmc1 = mapper_for(rom(1, 8, 1, 0));
+    mmc1_write(&mut *mmc1, 0xE000, 3);
+    assert_eq!(mmc1.cpu_read(0x8000), 3);
+    assert_eq!(mmc1.cpu_read(0xC000), 7);
+
+    // first bank fixed at $8000
+    mmc1_write(&mut *mmc1, 0x8000, 0b0_1000);
+    assert_eq!(mmc1.cpu_read(0x8000), 0);
+    assert_eq!(mmc1.cpu_read(0xC000), 3);
+
+    // 32 KiB, low bit of the bank ignored
+    mmc1_write(&mut *mmc1, 0x8000, 0b0_0000);
+    assert_eq!(mmc1.cpu_read(0x8000), 2);
+    assert_eq!(mmc1.cpu_read(0xC000), 3);
+}
+
+#[test]
+fn mmc1_bit_7_resets_the_shift_register() {
+    let mut mmc1 = mapper_for(rom(1, 8, 1, 0));
+    mmc1_write(&mut *mmc1, 0x8000, 0b0_1000);
+    // two stray bits, then a reset, which also fixes the last bank again
+    mmc1.cpu_write(0xE000, 1);
+    mmc1.cpu_cycle();
+    mmc1.cpu_cycle();
+    mmc1.cpu_write(0xE000, 1);
+    mmc1.cpu_cycle();
+    mmc1.cpu_cycle();
+    mmc1.cpu_write(0x8000, 0x80);
+    mmc1.cpu_cycle();
+    mmc1.cpu_cycle();
+    assert_eq!(mmc1.cpu_read(0xC000), 7);
+
+    mmc1_write(&mut *mmc1, 0xE000, 5);
+    assert_eq!(mmc1.cpu_read(0x8000), 5);
+}
+
+#[test]
+fn mmc1_ignores_writes_on_consecutive_cycles() {
+    let mut mmc1 = mapper_for(rom(1, 8, 1, 0));
+    // a read-modify-write instruction writes the old value, then the new
+    // one a cycle later; only the first counts
+    for bit in [1, 0, 0, 0, 0] {
+        mmc1.cpu_write(0xE000, bit);
+        mmc1.cpu_cycle();
+        mmc1.cpu_write(0xE000, 0x80);
+        mmc1.cpu_cycle();
+        mmc1.cpu_cycle();
+    }
+    assert_eq!(mmc1.cpu_read(0x8000), 1);
+}
+
+#[test]
+fn mmc1_sees_one_write_from_inc() {
+    // INC $FFF0 writes the fixed last bank's 15 back, then 16; four
+    // STA $FFF0 of 1 follow
+    let program = [
+        0xEE, 0xF0, 0xFF, 0xA9, 0x01, 0x8D, 0xF0, 0xFF, 0x8D, 0xF0, 0xFF, 0x8D, 0xF0, 0xFF, 0x8D,
+        0xF0, 0xFF,
+    ];
+    for cycle_stepped in [false, true] {
+        let mut cpu = CPU::new(Bus::with_rom(rom(1, 16, 1, 0)).unwrap());
+        cpu.cycle_stepped = cycle_stepped;
+        for (i, byte) in program.iter().enumerate() {
+            cpu.mem_write(0x0200 + i as u16, *byte);
+        }
+        cpu.program_counter = 0x0200;
+        for _ in 0..6 {
+            cpu.step().unwrap();
+        }
+        // 1, 1, 1, 1, 1 rather than 1, 0, 1, 1, 1
+        assert_eq!(cpu.mem_peek(0x8000), 15);
+    }
+}
+
+#[test]
+fn mmc1_resets_on_inc_of_a_byte_with_bit_7_set() {
+    // the usual reset idiom: INC on a ROM byte of $FF writes $FF, then $00
+    let mut raw = image(1, 16, 1, 0);
+    raw[16 + 15 * 0x4000 + 0x3FFF] = 0xFF;
+    let mut cpu = CPU::new(Bus::with_rom(Rom::new(&raw).unwrap()).unwrap());
+    // STA $8000 of 0 twice, INC $FFFF, then five writes of 1 to $E000
+    let program = [
+        0xA9, 0x00, 0x8D, 0x00, 0x80, 0x8D, 0x00, 0x80, 0xEE, 0xFF, 0xFF, 0xA9, 0x01, 0x8D, 0x00,
+        0xE0, 0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0,
+    ];
+    for (i, byte) in program.iter().enumerate() {
+        cpu.mem_write(0x0200 + i as u16, *byte);
+    }
+    cpu.program_counter = 0x0200;
+    for _ in 0..10 {
+        cpu.step().unwrap();
+    }
+    // the reset dropped the two stray bits, so the five 1s land in PRG
+    assert_eq!(cpu.mem_peek(0x8000), 15);
+}
+
+#[test]
+fn mmc1_chr_banking() {
+    let mut mmc1 = mapper_for(rom(1, 2, 4, 0));
+    // 8 KiB mode: bank 3 loses its low bit and maps 4 KiB banks 2 and 3
+    mmc1_write(&mut *mmc1, 0xA000, 3);
+    assert_eq!(mmc1.ppu_read(0x0000), 0x81);
+    assert_eq!(mmc1.ppu_read(0x1000), 0x81);
+
+    mmc1_write(&mut *mmc1, 0x8000, 0b1_1100);
+    mmc1_write(&mut *mmc1, 0xA000, 5);
+    mmc1_write(&mut *mmc1, 0xC000, 0);
+    assert_eq!(mmc1.ppu_read(0x0000), 0x82);
+    assert_eq!(mmc1.ppu_read(0x1000), 0x80);
+}
+
+#[test]
+fn mmc1_mirroring_is_software_controlled() {
+    let mut mmc1 = mapper_for(rom(1, 2, 1, 0));
+    for (control, mirroring) in [
+        (0, Mirroring::SingleScreenLower),
+        (1, Mirroring::SingleScreenUpper),
+        (2, Mirroring::Vertical),
+        (3, Mirroring::Horizontal),
+    ] {
+        mmc1_write(&mut *mmc1, 0x8000, 0b0_1100 | control);
+        assert_eq!(mmc1.mirroring(), mirroring);
+    }
+}
+
+#[test]
+fn mmc1_prg_ram_enable_and_battery() {
+    let mut mmc1 = mapper_for(rom(1, 2, 1, 0b10));
+    mmc1.cpu_write(0x6000, 0x12);
+    assert_eq!(mmc1.cpu_read(0x6000), 0x12);
+
+    mmc1_write(&mut *mmc1, 0xE000, 0b1_0000);
+    assert_eq!(mmc1.cpu_read(0x6000), 0);
+    mmc1.cpu_write(0x6000, 0x34);
+    mmc1_write(&mut *mmc1, 0xE000, 0);
+    assert_eq!(mmc1.cpu_read(0x6000), 0x12);
+
+    let saved = mmc1.battery_ram().unwrap();
+    assert_eq!(saved.len(), 0x2000);
+    assert_eq!(saved[0], 0x12);
+    mmc1.battery_ram_mut().unwrap()[1] = 0x56;
+    assert_eq!(mmc1.cpu_read(0x6001), 0x56);
+
+    assert!(mapper_for(rom(1, 2, 1, 0)).battery_ram().is_none());
+}
+
+#[test]
+fn surom_selects_the_prg_half_with_chr_bit_4() {
+    let mut mmc1 = mapper_for(rom(1, 32, 0, 0));
+    assert_eq!(mmc1.cpu_read(0xC000), 15);
+    mmc1_write(&mut *mmc1, 0xA000, 0b1_0000);
+    assert_eq!(mmc1.cpu_read(0x8000), 16);
+    assert_eq!(mmc1.cpu_read(0xC000), 31);
+}
+
+#[test]
+fn sorom_banks_prg_ram_with_chr_bit_3() {
+    // 16 KiB of PRG RAM
+    let mut raw = image(1, 2, 0, 0b10);
+    raw[8] = 2;
+    let mut mmc1 = mapper_for(Rom::new(&raw).unwrap());
+    mmc1_write(&mut *mmc1, 0xA000, 0b0_1000);
+    mmc1.cpu_write(0x6000, 0x22);
+    mmc1_write(&mut *mmc1, 0xA000, 0b0_0100);
+    assert_eq!(mmc1.cpu_read(0x6000), 0);
+    assert_eq!(mmc1.battery_ram().unwrap()[0x2000], 0x22);
+}
+
+#[test]
+fn sxrom_banks_prg_ram_with_chr_bits_3_and_2() {
+    // 32 KiB of PRG RAM; bit 3 is the low bank bit, bit 2 the high one
+    let mut raw = image(1, 2, 0, 0b10);
+    raw[8] = 4;
+    let mut mmc1 = mapper_for(Rom::new(&raw).unwrap());
+    let select = |bank: u8| (bank & 1) << 3 | (bank >> 1) << 2;
+    for bank in 0..4 {
+        mmc1_write(&mut *mmc1, 0xA000, select(bank));
+        mmc1.cpu_write(0x6000, 0x10 + bank);
+    }
+    for bank in 0..4 {
+        mmc1_write(&mut *mmc1, 0xA000, select(bank));
+        assert_eq!(mmc1.cpu_read(0x6000), 0x10 + bank);
+        assert_eq!(
+            mmc1.battery_ram().unwrap()[bank as usize * 0x2000],
+            0x10 + bank
+        );
+    }
+}
+
+#[test]
+fn snrom_disables_prg_ram_with_chr_bit_4() {
+    // 8 KiB each of CHR RAM and PRG RAM, 256 KiB of PRG ROM
+    let mut mmc1 = mapper_for(rom(1, 16, 0, 0b10));
+    mmc1.cpu_write(0x6000, 0x12);
+    mmc1_write(&mut *mmc1, 0xA000, 0b1_0000);
+    assert_eq!(mmc1.cpu_read(0x6000), 0);
+    mmc1.cpu_write(0x6000, 0x34);
+    mmc1_write(&mut *mmc1, 0xA000, 0);
+    assert_eq!(mmc1.cpu_read(0x6000), 0x12);
+
+    // with 512 KiB the bit picks the PRG half instead
+    let mut mmc1 = mapper_for(rom(1, 32, 0, 0b10));
+    mmc1.cpu_write(0x6000, 0x12);
+    mmc1_write(&mut *mmc1, 0xA000, 0b1_0000);
+    assert_eq!(mmc1.cpu_read(0x6000), 0x12);
+}
+
+#[test]
+fn battery_ram_is_only_the_nvram() {
+    // NES 2.0: 8 KiB of volatile PRG RAM, then 8 KiB kept by the battery
+    let mut raw = image(1, 2, 0, 0b10);
+    raw[7] |= 0x08;
+    raw[10] = 0x77;
+    raw[11] = 0x07;
+    let mut mmc1 = mapper_for(Rom::new(&raw).unwrap());
+    mmc1.cpu_write(0x6000, 0x11);
+    mmc1_write(&mut *mmc1, 0xA000, 0b0_1000);
+    mmc1.cpu_write(0x6000, 0x22);
+    let saved = mmc1.battery_ram().unwrap();
+    assert_eq!(saved.len(), 0x2000);
+    assert_eq!(saved[0], 0x22);
+
+    mmc1.power_on();
+    assert_eq!(mmc1.cpu_read(0x6000), 0);
+    mmc1_write(&mut *mmc1, 0xA000, 0b0_1000);
+    assert_eq!(mmc1.cpu_read(0x6000), 0x22);
+}
+
+#[test]
+fn mmc1_reset_keeps_prg_ram_and_power_on_clears_it() {
+    let mut bus = Bus::with_rom(rom(1, 8, 1, 0)).unwrap();
+    bus.mem_write(0x6000, 0x12);
+    bus.reset();
+    assert_eq!(bus.mem_peek(0x6000), 0x12);
+    bus.power_on();
+    assert_eq!(bus.mem_peek(0x6000), 0);
+}
+
+#[test]
+fn bus_reset_and_power_on_reach_the_mapper() {
+    let mut bus = Bus::with_rom(rom(1, 8, 1, 0)).unwrap();
+    let mmc1 = bus.mapper_mut().unwrap();
+    mmc1_write(mmc1, 0xE000, 3);
+    mmc1_write(mmc1, 0x8000, 0b0_1000);
+    // two stray bits left in the shift register
+    mmc1.cpu_write(0xE000, 1);
+    for _ in 0..4 {
+        mmc1.cpu_cycle();
+    }
+    mmc1.cpu_write(0xE000, 1);
+    for _ in 0..4 {
+        mmc1.cpu_cycle();
+    }
+
+    // reset empties the shift register and fixes the last bank again
+    bus.reset();
+    assert_eq!(bus.mem_peek(0xC000), 7);
+    mmc1_write(bus.mapper_mut().unwrap(), 0xE000, 5);
+    assert_eq!(bus.mem_peek(0x8000), 5);
+
+    bus.power_on();
+    assert_eq!(bus.mem_peek(0x8000), 0);
+}
+
+#[test]
+fn power_on_clears_prg_ram_and_reset_keeps_it() {
+    let mut bus = Bus::with_rom(rom(0, 1, 1, 0)).unwrap();
+    bus.mem_write(0x6000, 0x12);
+    bus.reset();
+    assert_eq!(bus.mem_peek(0x6000), 0x12);
+    bus.power_on();
+    assert_eq!(bus.mem_peek(0x6000), 0);
+
+    // what the battery keeps survives both
+    let mut bus = Bus::with_rom(rom(0, 1, 1, 0b10)).unwrap();
+    bus.mem_write(0x6000, 0x12);
+    bus.power_on();
+    assert_eq!(bus.mem_peek(0x6000), 0x12);
+}
+
+#[test]
+fn power_on_loads_the_trainer_again() {
+    let mut raw = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0b100, 0];
+    raw.resize(16, 0);
+    raw.extend(std::iter::repeat_n(0x77, 512));
+    raw.extend(std::iter::repeat_n(0, 0x4000 + 0x2000));
+    let mut bus = Bus::with_rom(Rom::new(&raw).unwrap()).unwrap();
+    assert_eq!(bus.mem_peek(0x7000), 0x77);
+    bus.mem_write(0x7000, 0x12);
+    bus.mem_write(0x6000, 0x34);
+    bus.power_on();
+    assert_eq!(bus.mem_peek(0x7000), 0x77);
+    assert_eq!(bus.mem_peek(0x6000), 0);
+}
+
+// A cartridge with a cycle counter that raises IRQ after 100 CPU cycles and
+// drops it when $6000 is written, counting the acknowledgements.
+struct IrqStub {
+    prg: Vec<u8>,
+    cycles: u64,
+    acks: u8,
+}
+
+impl Mapper for IrqStub {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000 => self.acks,
+            0x8000..=0xFFFF => self.prg[(addr - 0x8000) as usize],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, _data: u8) {
+        if addr == 0x6000 {
+            self.acks += 1;
+            self.cycles = 0;
+        }
+    }
+
+    fn ppu_read(&mut self, _addr: u16) -> u8 {
+        0
+    }
+
+    fn ppu_write(&mut self, _addr: u16, _data: u8) {}
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn irq(&self) -> bool {
+        self.acks == 0 && self.cycles >= 100
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycles += 1;
+    }
+}
+
+impl IrqStub {
+    fn new() -> Self {
+        let mut prg = vec![0xEA; 0x8000];
+        // $8000: CLI; loop: JMP loop
+        prg[..4].copy_from_slice(&[0x58, 0x4C, 0x01, 0x80]);
+        // $9000: STA $6000; RTI
+        prg[0x1000..0x1004].copy_from_slice(&[0x8D, 0x00, 0x60, 0x40]);
+        prg[0x7FFC..].copy_from_slice(&[0x00, 0x80, 0x00, 0x90]);
+        IrqStub {
+            prg,
+            cycles: 0,
+            acks: 0,
+        }
+    }
+}
+
+#[test]
+fn mapper_irq_reaches_the_cpu() {
+    for cycle_stepped in [false, true] {
+        let mut cpu = CPU::new(Bus::with_mapper(Box::new(IrqStub::new())));
+        cpu.cycle_stepped = cycle_stepped;
+        cpu.power_on();
+        for _ in 0..20 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.mem_peek(0x6000), 0);
+        for _ in 0..100 {
+            cpu.step().unwrap();
+        }
+        // taken once; the handler's write released the line
+        assert_eq!(cpu.mem_peek(0x6000), 1);
+        assert!(!cpu.bus.irq());
+    }
+}
+
+#[test]
+fn uxrom_switches_8000_and_has_bus_conflicts() {
+    let mut uxrom = mapper_for(rom(2, 8, 0, 0));
+    assert_eq!(uxrom.cpu_read(0xC000), 7);
+    // $C000 holds 7, so 3 gets through
+    uxrom.cpu_write(0xC000, 3);
+    assert_eq!(uxrom.cpu_read(0x8000), 3);
+    assert_eq!(uxrom.cpu_read(0xC000), 7);
+    // $8000 now holds 3: 4 & 3 selects bank 0
+    uxrom.cpu_write(0x8000, 4);
+    assert_eq!(uxrom.cpu_read(0x8000), 0);
+
+    // submapper 1 is a board without them
+    let mut raw = image(2, 8, 0, 0);
+    nes2(&mut raw, 1);
+    let mut uxrom = mapper_for(Rom::new(&raw).unwrap());
+    uxrom.cpu_write(0x8000, 4);
+    assert_eq!(uxrom.cpu_read(0x8000), 4);
+}
+
+#[test]
+fn cnrom_switches_chr() {
+    let mut raw = image(3, 1, 4, 0);
+    nes2(&mut raw, 1);
+    let mut cnrom = mapper_for(Rom::new(&raw).unwrap());
+    assert_eq!(cnrom.ppu_read(0x1FFF), 0x80);
+    cnrom.cpu_write(0x8000, 2);
+    assert_eq!(cnrom.ppu_read(0x0000), 0x82);
+    assert_eq!(cnrom.ppu_read(0x1FFF), 0x82);
+    // 16 KiB of PRG mirrored, as on NROM
+    assert_eq!(cnrom.cpu_read(0xC000), 0);
+}
+
+#[test]
+fn axrom_switches_32k_and_the_screen() {
+    let mut axrom = mapper_for(rom(7, 8, 0, 0));
+    assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+    axrom.cpu_write(0x8000, 0x12);
+    assert_eq!(axrom.cpu_read(0x8000), 4);
+    assert_eq!(axrom.cpu_read(0xC000), 5);
+    assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+    axrom.cpu_write(0x8000, 0x00);
+    assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+}
+
+#[test]
+fn color_dreams_switches_prg_and_chr() {
+    let mut color_dreams = mapper_for(rom(11, 4, 4, 0));
+    color_dreams.cpu_write(0x8000, 0x21);
+    assert_eq!(color_dreams.cpu_read(0x8000), 2);
+    assert_eq!(color_dreams.ppu_read(0x0000), 0x82);
+}
+
+#[test]
+fn gxrom_switches_prg_and_chr() {
+    let mut gxrom = mapper_for(rom_with_ff_at_ffff(66, 4, 4));
+    gxrom.cpu_write(0xFFFF, 0x12);
+    assert_eq!(gxrom.cpu_read(0x8000), 2);
+    assert_eq!(gxrom.cpu_read(0xC000), 3);
+    assert_eq!(gxrom.ppu_read(0x1000), 0x82);
+}
+
+#[test]
+fn bnrom_switches_32k_with_bus_conflicts() {
+    let mut bnrom = mapper_for(rom_with_ff_at_ffff(34, 4, 0));
+    bnrom.cpu_write(0xFFFF, 1);
+    assert_eq!(bnrom.cpu_read(0x8000), 2);
+    // $8000 holds 2 now: 1 & 2 selects bank 0
+    bnrom.cpu_write(0x8000, 1);
+    assert_eq!(bnrom.cpu_read(0x8000), 0);
+}
+
+#[test]
+fn nina_001_has_registers_over_prg_ram() {
+    let rom = rom(34, 4, 2, 0);
+    assert_eq!(Discrete::new(rom.clone()).unwrap().board(), Board::Nina001);
+    let mut nina = mapper_for(rom);
+    nina.cpu_write(0x7FFD, 1);
+    nina.cpu_write(0x7FFE, 3);
+    nina.cpu_write(0x7FFF, 0);
+    assert_eq!(nina.cpu_read(0x8000), 2);
+    assert_eq!(nina.ppu_read(0x0000), 0x81);
+    assert_eq!(nina.ppu_read(0x1000), 0x80);
+    assert_eq!(nina.cpu_read(0x7FFD), 1);
+    // no latch at $8000
+    nina.cpu_write(0x8000, 0);
+    assert_eq!(nina.cpu_read(0x8000), 2);
+}
+
+#[test]
+fn prg_smaller_than_a_bank_mirrors() {
+    // NES 2.0 exponent form: 2^13 bytes
+    let mut raw = image(2, 1, 0, 0);
+    nes2(&mut raw, 0);
+    raw[4] = 13 << 2;
+    raw[9] = 0x0F;
+    raw[16 + 0x1FFF] = 0x42;
+    let mut uxrom = mapper_for(Rom::new(&raw).unwrap());
+    assert_eq!(uxrom.cpu_read(0x9FFF), 0x42);
+    assert_eq!(uxrom.cpu_read(0xBFFF), 0x42);
+    assert_eq!(uxrom.cpu_read(0xFFFF), 0x42);
+}
+
+#[test]
+fn latch_survives_reset_but_not_power_on() {
+    let mut bus = Bus::with_rom(rom(2, 8, 0, 0)).unwrap();
+    bus.mem_write(0xC000, 3);
+    bus.reset();
+    assert_eq!(bus.mem_peek(0x8000), 3);
+    bus.power_on();
+    assert_eq!(bus.mem_peek(0x8000), 0);
+}
@@ -0,0 +1,54 @@
+// Runs nestest.nes in automation mode and compares every traced instruction
+// against the reference nestest.log. Both files live in tests/fixtures and are
+// not shipped with the crate, so the test is ignored by default; run it with
+// `cargo test --test nestest -- --ignored` once they are in place.
+
+use nes_emulator::bus::Bus;
+use nes_emulator::cartridge::Rom;
+use nes_emulator::cpu::CPU;
+use nes_emulator::mem::Mem;
+use nes_emulator::trace;
+use std::fs;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/nestest.nes and nestest.log"]
+fn nestest_matches_golden_log() {
+    let rom = fs::read(fixture("nestest.nes")).expect("tests/fixtures/nestest.nes");
+    let log = fs::read_to_string(fixture("nestest.log")).expect("tests/fixtures/nestest.log");
+
+    // NROM: a single 16K bank is mirrored into $C000-$FFFF
+    let bus = Bus::with_rom(Rom::new(&rom).unwrap()).unwrap();
+    let mut cpu = CPU::new(bus);
+    cpu.program_counter = 0xC000;
+    cpu.status = 0x24;
+    cpu.stp = 0xFD;
+    cpu.cycles = 7;
+
+    for (number, expected) in log.lines().enumerate() {
+        let expected = expected.trim_end();
+        let actual = trace::trace(&cpu).to_string();
+        assert_eq!(
+            actual,
+            expected,
+            "first divergence at nestest.log line {}\n  expected: {}\n    actual: {}",
+            number + 1,
+            expected,
+            actual
+        );
+        if let Err(e) = cpu.step() {
+            panic!("line {}: {}", number + 1, e);
+        }
+    }
+
+    // nestest reports the failing official/unofficial test number here
+    assert_eq!(cpu.mem_read(0x02), 0, "nestest official opcode error code");
+    assert_eq!(cpu.mem_read(0x03), 0, "nestest unofficial opcode error code");
+}
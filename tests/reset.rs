@@ -0,0 +1,66 @@
+use nes_emulator::bus::{Bus, RamInit};
+use nes_emulator::constants::StatusFlag;
+use nes_emulator::cpu::CPU;
+use nes_emulator::mem::Mem;
+
+fn cpu_with_vector(ram_init: RamInit) -> CPU<Bus> {
+    let mut cpu = CPU::new(Bus::with_ram_init(ram_init));
+    cpu.mem_write_u16(0xfffc, 0x8000);
+    cpu
+}
+
+#[test]
+fn power_on_state() {
+    let mut cpu = cpu_with_vector(RamInit::Zeros);
+    cpu.reg_a = 1;
+    cpu.reg_y = 2;
+    cpu.power_on();
+
+    assert_eq!((cpu.reg_a, cpu.reg_x, cpu.reg_y), (0, 0, 0));
+    assert_eq!(cpu.stp, 0xfd);
+    assert_eq!(cpu.status, 0x24);
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.cycles, 7);
+}
+
+#[test]
+fn soft_reset_keeps_registers_and_ram() {
+    let mut cpu = cpu_with_vector(RamInit::Zeros);
+    cpu.power_on();
+    cpu.reg_a = 0x11;
+    cpu.reg_x = 0x22;
+    cpu.reg_y = 0x33;
+    cpu.status = 0x00;
+    cpu.program_counter = 0x1234;
+    cpu.mem_write(0x0300, 0x99);
+    let cycles = cpu.cycles;
+
+    cpu.reset();
+
+    assert_eq!((cpu.reg_a, cpu.reg_x, cpu.reg_y), (0x11, 0x22, 0x33));
+    assert_eq!(cpu.stp, 0xfa);
+    assert!(cpu.check_flag(StatusFlag::InterruptDisable));
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.cycles, cycles + 7);
+    assert_eq!(cpu.mem_read(0x0300), 0x99);
+}
+
+#[test]
+fn power_on_fills_ram() {
+    let mut cpu = cpu_with_vector(RamInit::Ones);
+    cpu.mem_write(0x0300, 0x00);
+    cpu.power_on();
+    assert_eq!(cpu.mem_read(0x0300), 0xff);
+    assert_eq!(cpu.mem_read(0x0000), 0xff);
+}
+
+#[test]
+fn random_ram_is_reproducible_from_its_seed() {
+    let ram = |seed| {
+        let mut bus = Bus::with_ram_init(RamInit::Random(seed));
+        (0..0x800).map(|addr| bus.mem_read(addr)).collect::<Vec<u8>>()
+    };
+    assert_eq!(ram(1), ram(1));
+    assert_ne!(ram(1), ram(2));
+    assert!(ram(1).iter().any(|&byte| byte != ram(1)[0]));
+}
@@ -0,0 +1,129 @@
+use nes_emulator::asm::assemble;
+use nes_emulator::cpu::{CPU, RunControl};
+use nes_emulator::mem::{FlatMemory, Mem};
+use nes_emulator::runner::{StopCondition, Stopped, WriteWatch, hex_dump, registers, run};
+
+fn cpu_with(source: &str) -> CPU<WriteWatch<FlatMemory>> {
+    let mut cpu = CPU::new(WriteWatch::new(FlatMemory::new()));
+    cpu.load(assemble(source).unwrap());
+    cpu
+}
+
+// counts X down from 3, stores it, then stops on one of several endings
+const PROGRAM: &str = "
+        ldx #3
+    loop:
+        stx $10
+        dex
+        bne loop
+        lda #$42
+        sta $20
+    done:
+        jmp done
+";
+
+#[test]
+fn stops_when_pc_reaches_an_address() {
+    let mut cpu = cpu_with(PROGRAM);
+    let stopped = run(&mut cpu, &[StopCondition::Pc(0x0607)]).unwrap();
+    assert_eq!(stopped.condition, StopCondition::Pc(0x0607));
+    assert_eq!(cpu.program_counter, 0x0607);
+    // LDX, then three rounds of STX/DEX/BNE
+    assert_eq!(stopped.instructions, 10);
+    assert_eq!(cpu.reg_a, 0x00);
+}
+
+#[test]
+fn stops_on_a_self_loop() {
+    let mut cpu = cpu_with(PROGRAM);
+    let stopped = run(&mut cpu, &[StopCondition::SelfLoop]).unwrap();
+    assert_eq!(stopped.condition, StopCondition::SelfLoop);
+    assert_eq!(cpu.program_counter, 0x060b);
+    assert_eq!(cpu.mem_peek(0x20), 0x42);
+}
+
+#[test]
+fn stops_after_instructions_or_cycles() {
+    let mut cpu = cpu_with(PROGRAM);
+    let stopped = run(&mut cpu, &[StopCondition::Instructions(4)]).unwrap();
+    assert_eq!(stopped.instructions, 4);
+    assert_eq!(cpu.program_counter, 0x0602);
+
+    let mut cpu = cpu_with(PROGRAM);
+    let stopped = run(&mut cpu, &[StopCondition::Cycles(5)]).unwrap();
+    // LDX #imm (2) then STX zp (3)
+    assert_eq!(
+        stopped,
+        Stopped {
+            condition: StopCondition::Cycles(5),
+            instructions: 2,
+            cycles: 5,
+        }
+    );
+}
+
+#[test]
+fn stops_before_brk() {
+    let mut cpu = cpu_with("lda #1\n brk");
+    let stopped = run(&mut cpu, &[StopCondition::Brk]).unwrap();
+    assert_eq!(stopped.condition, StopCondition::Brk);
+    assert_eq!(cpu.program_counter, 0x0602);
+    assert_eq!(stopped.instructions, 1);
+}
+
+#[test]
+fn stops_on_a_watched_write() {
+    let mut cpu = cpu_with(PROGRAM);
+    let conditions = [StopCondition::SelfLoop, StopCondition::Write(0x20)];
+    let stopped = run(&mut cpu, &conditions).unwrap();
+    assert_eq!(stopped.condition, StopCondition::Write(0x20));
+    // stopped right after the STA
+    assert_eq!(cpu.program_counter, 0x060b);
+}
+
+#[test]
+fn conditions_already_true_stop_before_running() {
+    let mut cpu = cpu_with(PROGRAM);
+    let stopped = run(&mut cpu, &[StopCondition::Pc(0x0600)]).unwrap();
+    assert_eq!(stopped.instructions, 0);
+}
+
+#[test]
+fn cpu_errors_end_the_run() {
+    // a JAM opcode on the 2A03
+    let mut cpu = cpu_with(".byte $02");
+    assert!(run(&mut cpu, &[StopCondition::SelfLoop]).is_err());
+}
+
+#[test]
+fn run_until_returns_the_last_step() {
+    let mut cpu = CPU::new(FlatMemory::new());
+    cpu.load(assemble("inx\n inx\n inx").unwrap());
+    let info = cpu
+        .run_until(|cpu, _| {
+            if cpu.reg_x == 2 {
+                RunControl::Stop
+            } else {
+                RunControl::Continue
+            }
+        })
+        .unwrap();
+    assert_eq!(info.counter, 0x0601);
+    assert_eq!(cpu.program_counter, 0x0602);
+}
+
+#[test]
+fn formats_registers_and_memory() {
+    let mut cpu = CPU::new(FlatMemory::new());
+    cpu.reg_a = 0x12;
+    cpu.program_counter = 0xc000;
+    assert_eq!(registers(&cpu), "A:12 X:00 Y:00 P:24 SP:FD PC:C000 CYC:0");
+
+    for i in 0..18u16 {
+        cpu.mem_write(0x0200 + i, i as u8);
+    }
+    assert_eq!(
+        hex_dump(&cpu, 0x0200, 0x0211),
+        "0200: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F\n0210: 10 11\n"
+    );
+}
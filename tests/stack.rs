@@ -0,0 +1,110 @@
+use nes_emulator::cpu::CPU;
+use nes_emulator::mem::{FlatMemory, Mem};
+use nes_emulator::trace::{Diagnostic, TraceEntry, TraceSink};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn cpu_at(program: &[u8], stp: u8) -> CPU<FlatMemory> {
+    let mut cpu = CPU::new(FlatMemory::new());
+    for (i, byte) in program.iter().enumerate() {
+        cpu.mem_write(0x0200 + i as u16, *byte);
+    }
+    cpu.program_counter = 0x0200;
+    cpu.stp = stp;
+    cpu
+}
+
+#[derive(Clone, Default)]
+struct Diagnostics(Rc<RefCell<Vec<Diagnostic>>>);
+
+impl TraceSink for Diagnostics {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.0.borrow_mut().push(*diagnostic);
+    }
+}
+
+#[test]
+fn push_writes_then_decrements() {
+    // LDA #$42; PHA
+    let mut cpu = cpu_at(&[0xa9, 0x42, 0x48], 0xfd);
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.mem_peek(0x01fd), 0x42);
+    assert_eq!(cpu.stp, 0xfc);
+}
+
+#[test]
+fn pull_increments_then_reads() {
+    // PLA
+    let mut cpu = cpu_at(&[0x68], 0xfc);
+    cpu.mem_write(0x01fd, 0x42);
+    cpu.step().unwrap();
+    assert_eq!(cpu.reg_a, 0x42);
+    assert_eq!(cpu.stp, 0xfd);
+}
+
+#[test]
+fn push_wraps_inside_page_one() {
+    // LDA #$42; PHA; PHA
+    let mut cpu = cpu_at(&[0xa9, 0x42, 0x48, 0x48], 0x00);
+    for _ in 0..3 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(cpu.mem_peek(0x0100), 0x42);
+    assert_eq!(cpu.mem_peek(0x01ff), 0x42);
+    assert_eq!(cpu.mem_peek(0x0200), 0xa9);
+    assert_eq!(cpu.stp, 0xfe);
+}
+
+#[test]
+fn pull_wraps_inside_page_one() {
+    // PLA
+    let mut cpu = cpu_at(&[0x68], 0xff);
+    cpu.mem_write(0x0100, 0x42);
+    cpu.step().unwrap();
+    assert_eq!(cpu.reg_a, 0x42);
+    assert_eq!(cpu.stp, 0x00);
+}
+
+#[test]
+fn jsr_and_rts_across_the_wrap() {
+    // JSR $0300 with SP = $00; the subroutine is a lone RTS
+    let mut cpu = cpu_at(&[0x20, 0x00, 0x03], 0x00);
+    cpu.mem_write(0x0300, 0x60);
+    cpu.step().unwrap();
+    assert_eq!(cpu.mem_peek(0x0100), 0x02);
+    assert_eq!(cpu.mem_peek(0x01ff), 0x02);
+    cpu.step().unwrap();
+    assert_eq!(cpu.program_counter, 0x0203);
+    assert_eq!(cpu.stp, 0x00);
+}
+
+#[test]
+fn wraparound_is_reported_only_when_asked() {
+    // PHA wraps SP to $FF, the first PLA wraps it back
+    let program = [0x48, 0x68, 0x68];
+    let sink = Diagnostics::default();
+
+    let mut quiet = cpu_at(&program, 0x00);
+    quiet.set_tracer(sink.clone());
+    for _ in 0..3 {
+        quiet.step().unwrap();
+    }
+    assert!(sink.0.borrow().is_empty());
+
+    let mut cpu = cpu_at(&program, 0x00);
+    cpu.diagnostics = true;
+    cpu.set_tracer(sink.clone());
+    for _ in 0..3 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(
+        *sink.0.borrow(),
+        vec![
+            Diagnostic::StackOverflow { counter: 0x0200 },
+            Diagnostic::StackUnderflow { counter: 0x0201 },
+        ]
+    );
+}
@@ -0,0 +1,100 @@
+// step() reports what it executed, and errors leave the CPU where the
+// failing instruction started.
+
+use nes_emulator::constants::{CpuError, InterruptType};
+use nes_emulator::cpu::{CPU, CpuVariant};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+fn cpu_at(program: &[u8], cycle_stepped: bool) -> CPU<FlatMemory> {
+    let mut cpu = CPU::new(FlatMemory::new());
+    for (i, byte) in program.iter().enumerate() {
+        cpu.mem_write(0x0200 + i as u16, *byte);
+    }
+    cpu.program_counter = 0x0200;
+    cpu.cycle_stepped = cycle_stepped;
+    cpu
+}
+
+#[test]
+fn step_info_describes_the_instruction() {
+    for cycle_stepped in [false, true] {
+        // LDA #$01; JMP $0300
+        let mut cpu = cpu_at(&[0xa9, 0x01, 0x4c, 0x00, 0x03], cycle_stepped);
+        let info = cpu.step().unwrap();
+        assert_eq!((info.counter, info.opcode, info.cycles), (0x0200, 0xa9, 2));
+        assert_eq!(info.interrupt, None);
+
+        let info = cpu.step().unwrap();
+        assert_eq!((info.counter, info.opcode, info.cycles), (0x0202, 0x4c, 3));
+        assert_eq!(cpu.program_counter, 0x0300);
+        assert_eq!(cpu.cycles, 5);
+    }
+}
+
+#[test]
+fn step_info_reports_an_interrupt_taken_first() {
+    for cycle_stepped in [false, true] {
+        // NOP, with a NOP at the NMI handler
+        let mut cpu = cpu_at(&[0xea], cycle_stepped);
+        cpu.mem_write_u16(0xfffa, 0x0300);
+        cpu.mem_write(0x0300, 0xea);
+        cpu.trigger_nmi();
+        let info = cpu.step().unwrap();
+        // counter and opcode are the handler's first instruction
+        assert_eq!((info.counter, info.opcode, info.cycles), (0x0300, 0xea, 9));
+        assert_eq!(info.interrupt, Some(InterruptType::NMI));
+    }
+}
+
+#[test]
+fn jam_is_an_error_that_keeps_the_cpu_on_the_opcode() {
+    for cycle_stepped in [false, true] {
+        // LDA #$01; JAM
+        let mut cpu = cpu_at(&[0xa9, 0x01, 0x02], cycle_stepped);
+        cpu.step().unwrap();
+        for _ in 0..2 {
+            match cpu.step() {
+                Err(CpuError::Jam { opcode, counter }) => {
+                    assert_eq!((opcode, counter), (0x02, 0x0202));
+                }
+                other => panic!("expected a jam, got {:?}", other.map(|info| info.opcode)),
+            }
+            assert_eq!(cpu.program_counter, 0x0202);
+            assert_eq!(cpu.reg_a, 0x01);
+        }
+    }
+}
+
+#[test]
+fn every_opcode_decodes_on_every_variant() {
+    // jams are the only error a program can cause
+    for variant in [
+        CpuVariant::Ricoh2A03,
+        CpuVariant::Nmos6502,
+        CpuVariant::Cmos65C02,
+    ] {
+        for code in 0..=0xff {
+            let mut cpu = CPU::with_variant(FlatMemory::new(), variant);
+            cpu.mem_write(0x0200, code);
+            cpu.program_counter = 0x0200;
+            if let Err(e) = cpu.step() {
+                assert!(
+                    matches!(e, CpuError::Jam { .. }),
+                    "{:?} {:#04x}: {}",
+                    variant,
+                    code,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn errors_display_the_opcode_and_address() {
+    let error = CpuError::Jam {
+        opcode: 0x02,
+        counter: 0x0202,
+    };
+    assert_eq!(error.to_string(), "CPU jammed by opcode 0x02 at 0x0202");
+}
@@ -0,0 +1,122 @@
+// Trace lines follow nestest.log: bytes, disassembly with the resolved
+// address and value, registers, then the PPU and CPU cycle columns.
+
+use nes_emulator::cpu::CPU;
+use nes_emulator::mem::{FlatMemory, Mem};
+use nes_emulator::trace::{self, AddressFilter, TraceEntry};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// The trace line for the instruction at `pc`, with the registers nestest
+// starts from.
+fn line(pc: u16, program: &[u8], setup: impl Fn(&mut CPU<FlatMemory>)) -> String {
+    let mut cpu = CPU::new(FlatMemory::new());
+    for (i, byte) in program.iter().enumerate() {
+        cpu.mem_write(pc.wrapping_add(i as u16), *byte);
+    }
+    cpu.program_counter = pc;
+    cpu.status = 0x24;
+    cpu.stp = 0xfd;
+    cpu.cycles = 7;
+    setup(&mut cpu);
+    trace::trace(&cpu).to_string()
+}
+
+#[test]
+fn first_nestest_line() {
+    assert_eq!(
+        line(0xc000, &[0x4c, 0xf5, 0xc5], |_| {}),
+        "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+    );
+}
+
+#[test]
+fn operands_show_the_resolved_address_and_value() {
+    let cases: [(&[u8], &str); 10] = [
+        (&[0xa9, 0x80], "0400  A9 80     LDA #$80"),
+        (&[0x4a], "0400  4A        LSR A"),
+        (&[0xb0, 0xfb], "0400  B0 FB     BCS $03FD"),
+        (&[0x86, 0x10], "0400  86 10     STX $10 = 33"),
+        (&[0xb6, 0x10], "0400  B6 10     LDX $10,Y @ 12 = 55"),
+        (&[0xad, 0x00, 0x03], "0400  AD 00 03  LDA $0300 = 89"),
+        (
+            &[0xbd, 0xff, 0x02],
+            "0400  BD FF 02  LDA $02FF,X @ 0300 = 89",
+        ),
+        (
+            &[0xa1, 0x0f],
+            "0400  A1 0F     LDA ($0F,X) @ 10 = 0333 = 00",
+        ),
+        (
+            &[0xb1, 0x20],
+            "0400  B1 20     LDA ($20),Y = 02FF @ 0301 = 7A",
+        ),
+        (&[0x6c, 0xff, 0x02], "0400  6C FF 02  JMP ($02FF) = 0611"),
+    ];
+    for (program, asm) in cases {
+        let line = line(0x0400, program, |cpu| {
+            cpu.reg_x = 0x01;
+            cpu.reg_y = 0x02;
+            cpu.mem_write(0x10, 0x33);
+            cpu.mem_write(0x11, 0x03);
+            cpu.mem_write(0x12, 0x55);
+            cpu.mem_write_u16(0x20, 0x02ff);
+            // the NMOS indirect JMP takes its high byte from $0200
+            cpu.mem_write(0x0200, 0x06);
+            cpu.mem_write(0x02ff, 0x11);
+            cpu.mem_write(0x0300, 0x89);
+            cpu.mem_write(0x0301, 0x7a);
+        });
+        assert_eq!(format!("{:47}", asm), &line[..47], "{}", asm);
+        assert!(
+            line[47..].starts_with(" A:00 X:01 Y:02 P:24 SP:FD "),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn unofficial_mnemonics_take_the_space_before_them() {
+    assert_eq!(
+        line(0xc6bd, &[0x04, 0xa9], |_| {}),
+        "C6BD  04 A9    *NOP $A9 = 00                    A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+    );
+}
+
+#[test]
+fn ppu_column_follows_the_cycle_count() {
+    // 3 dots per cycle and 341 dots per scanline
+    for (cycles, ppu) in [
+        (0, "PPU:  0,  0 CYC:0"),
+        (113, "PPU:  0,339 CYC:113"),
+        (114, "PPU:  1,  1 CYC:114"),
+        (26554, "PPU:233,209 CYC:26554"),
+        // a frame is 262 scanlines
+        (29781, "PPU:  0,  1 CYC:29781"),
+    ] {
+        let line = line(0x0400, &[0xea], |cpu| cpu.cycles = cycles);
+        assert!(line.ends_with(ppu), "{}", line);
+    }
+}
+
+#[test]
+fn address_filter_drops_instructions_outside_the_range() {
+    let traced = Rc::new(RefCell::new(Vec::new()));
+    let sink = {
+        let traced = traced.clone();
+        move |entry: &TraceEntry| traced.borrow_mut().push(entry.counter)
+    };
+
+    // five NOPs, of which the middle ones are traced
+    let mut cpu = CPU::new(FlatMemory::new());
+    for addr in 0x0200..0x0205 {
+        cpu.mem_write(addr, 0xea);
+    }
+    cpu.program_counter = 0x0200;
+    cpu.set_tracer(AddressFilter::new(0x0201..=0x0203, sink));
+    for _ in 0..5 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(*traced.borrow(), vec![0x0201, 0x0202, 0x0203]);
+}
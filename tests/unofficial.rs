@@ -0,0 +1,286 @@
+// Results and flags of the stable unofficial NMOS opcodes, and the knobs
+// that pick a model for the unstable ones.
+
+use nes_emulator::constants::StatusFlag;
+use nes_emulator::cpu::{CPU, UnstableOpcodes};
+use nes_emulator::mem::{FlatMemory, Mem};
+
+// Runs the program's first instruction at $0200 in both atomic and
+// cycle-stepped mode and checks both agree before handing back the atomic CPU.
+fn run_one(program: &[u8], setup: impl Fn(&mut CPU<FlatMemory>)) -> CPU<FlatMemory> {
+    let mut cpus: Vec<CPU<FlatMemory>> = [false, true]
+        .iter()
+        .map(|&cycle_stepped| {
+            let mut cpu = CPU::new(FlatMemory::new());
+            for (i, byte) in program.iter().enumerate() {
+                cpu.mem_write(0x0200 + i as u16, *byte);
+            }
+            cpu.program_counter = 0x0200;
+            cpu.cycle_stepped = cycle_stepped;
+            setup(&mut cpu);
+            cpu.step().expect("instruction faulted");
+            cpu
+        })
+        .collect();
+    let stepped = cpus.pop().unwrap();
+    let atomic = cpus.pop().unwrap();
+    assert_eq!(
+        (atomic.reg_a, atomic.reg_x, atomic.stp, atomic.status),
+        (stepped.reg_a, stepped.reg_x, stepped.stp, stepped.status)
+    );
+    atomic
+}
+
+// (N, V, Z, C)
+fn flags(cpu: &CPU<FlatMemory>) -> (bool, bool, bool, bool) {
+    (
+        cpu.check_flag(StatusFlag::Negative),
+        cpu.check_flag(StatusFlag::Overflow),
+        cpu.check_flag(StatusFlag::Zero),
+        cpu.check_flag(StatusFlag::Carry),
+    )
+}
+
+#[test]
+fn lax_loads_a_and_x() {
+    // LAX $10
+    let cpu = run_one(&[0xa7, 0x10], |cpu| cpu.mem_write(0x10, 0x80));
+    assert_eq!((cpu.reg_a, cpu.reg_x), (0x80, 0x80));
+    assert_eq!(flags(&cpu), (true, false, false, false));
+}
+
+#[test]
+fn sax_stores_a_and_x_without_flags() {
+    // SAX $10
+    let cpu = run_one(&[0x87, 0x10], |cpu| {
+        cpu.reg_a = 0xf0;
+        cpu.reg_x = 0x3c;
+        cpu.status |= StatusFlag::Zero as u8;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x30);
+    assert_eq!(flags(&cpu), (false, false, true, false));
+}
+
+#[test]
+fn dcp_decrements_then_compares() {
+    // DCP $10
+    let cpu = run_one(&[0xc7, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x43);
+        cpu.reg_a = 0x42;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x42);
+    assert_eq!(cpu.reg_a, 0x42);
+    assert_eq!(flags(&cpu), (false, false, true, true));
+}
+
+#[test]
+fn isc_increments_then_subtracts() {
+    // ISC $10
+    let cpu = run_one(&[0xe7, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x0f);
+        cpu.reg_a = 0x20;
+        cpu.status |= StatusFlag::Carry as u8;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x10);
+    assert_eq!(cpu.reg_a, 0x10);
+    assert_eq!(flags(&cpu), (false, false, false, true));
+
+    // $70 - $80 borrows and overflows
+    let cpu = run_one(&[0xe7, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x7f);
+        cpu.reg_a = 0x70;
+        cpu.status |= StatusFlag::Carry as u8;
+    });
+    assert_eq!(cpu.reg_a, 0xf0);
+    assert_eq!(flags(&cpu), (true, true, false, false));
+}
+
+#[test]
+fn slo_shifts_left_then_ors() {
+    // SLO $10
+    let cpu = run_one(&[0x07, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x81);
+        cpu.reg_a = 0x01;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x02);
+    assert_eq!(cpu.reg_a, 0x03);
+    assert_eq!(flags(&cpu), (false, false, false, true));
+}
+
+#[test]
+fn rla_rotates_left_then_ands() {
+    // RLA $10
+    let cpu = run_one(&[0x27, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x81);
+        cpu.reg_a = 0xff;
+        cpu.status |= StatusFlag::Carry as u8;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x03);
+    assert_eq!(cpu.reg_a, 0x03);
+    assert_eq!(flags(&cpu), (false, false, false, true));
+}
+
+#[test]
+fn sre_shifts_right_then_eors() {
+    // SRE $10
+    let cpu = run_one(&[0x47, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x03);
+        cpu.reg_a = 0x01;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x01);
+    assert_eq!(cpu.reg_a, 0x00);
+    assert_eq!(flags(&cpu), (false, false, true, true));
+}
+
+#[test]
+fn rra_rotates_right_then_adds() {
+    // RRA $10: the carry the rotate shifts out feeds the add
+    let cpu = run_one(&[0x67, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x02);
+        cpu.reg_a = 0x10;
+        cpu.status |= StatusFlag::Carry as u8;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x81);
+    assert_eq!(cpu.reg_a, 0x91);
+    assert_eq!(flags(&cpu), (true, false, false, false));
+
+    let cpu = run_one(&[0x67, 0x10], |cpu| {
+        cpu.mem_write(0x10, 0x03);
+        cpu.reg_a = 0x7f;
+    });
+    assert_eq!(cpu.mem_peek(0x10), 0x01);
+    assert_eq!(cpu.reg_a, 0x81);
+    assert_eq!(flags(&cpu), (true, true, false, false));
+}
+
+#[test]
+fn anc_copies_n_into_c() {
+    // ANC #$80
+    let cpu = run_one(&[0x0b, 0x80], |cpu| cpu.reg_a = 0xff);
+    assert_eq!(cpu.reg_a, 0x80);
+    assert_eq!(flags(&cpu), (true, false, false, true));
+
+    let cpu = run_one(&[0x0b, 0x0f], |cpu| cpu.reg_a = 0xf0);
+    assert_eq!(cpu.reg_a, 0x00);
+    assert_eq!(flags(&cpu), (false, false, true, false));
+}
+
+#[test]
+fn alr_ands_then_shifts_right() {
+    // ALR #$03
+    let cpu = run_one(&[0x4b, 0x03], |cpu| cpu.reg_a = 0xff);
+    assert_eq!(cpu.reg_a, 0x01);
+    assert_eq!(flags(&cpu), (false, false, false, true));
+}
+
+#[test]
+fn arr_takes_c_from_bit_6_and_v_from_bits_6_and_5() {
+    // ARR #$FF with carry in
+    let cpu = run_one(&[0x6b, 0xff], |cpu| {
+        cpu.reg_a = 0xff;
+        cpu.status |= StatusFlag::Carry as u8;
+    });
+    assert_eq!(cpu.reg_a, 0xff);
+    assert_eq!(flags(&cpu), (true, false, false, true));
+
+    // ARR #$80: $40, bit 6 set and bit 5 clear
+    let cpu = run_one(&[0x6b, 0x80], |cpu| cpu.reg_a = 0xff);
+    assert_eq!(cpu.reg_a, 0x40);
+    assert_eq!(flags(&cpu), (false, true, false, true));
+
+    // ARR #$01: shifted out entirely
+    let cpu = run_one(&[0x6b, 0x01], |cpu| cpu.reg_a = 0xff);
+    assert_eq!(cpu.reg_a, 0x00);
+    assert_eq!(flags(&cpu), (false, false, true, false));
+}
+
+#[test]
+fn axs_subtracts_from_a_and_x_into_x() {
+    // AXS #$10
+    let cpu = run_one(&[0xcb, 0x10], |cpu| {
+        cpu.reg_a = 0xf0;
+        cpu.reg_x = 0x3c;
+    });
+    assert_eq!(cpu.reg_x, 0x20);
+    assert_eq!(cpu.reg_a, 0xf0);
+    assert_eq!(flags(&cpu), (false, false, false, true));
+
+    // borrowing clears C, and the decimal flag does not matter
+    let cpu = run_one(&[0xcb, 0x40], |cpu| {
+        cpu.reg_a = 0xf0;
+        cpu.reg_x = 0x3c;
+        cpu.status |= StatusFlag::DecimalMode as u8;
+    });
+    assert_eq!(cpu.reg_x, 0xf0);
+    assert_eq!(flags(&cpu), (true, false, false, false));
+}
+
+#[test]
+fn ane_and_lxa_follow_their_magic_constants() {
+    for magic in [0xee, 0x00, 0xff] {
+        // ANE #$FF
+        let cpu = run_one(&[0x8b, 0xff], |cpu| {
+            cpu.unstable.xaa_magic = magic;
+            cpu.reg_a = 0x11;
+            cpu.reg_x = 0x3f;
+        });
+        assert_eq!(cpu.reg_a, (0x11 | magic) & 0x3f);
+
+        // LXA #$5A
+        let cpu = run_one(&[0xab, 0x5a], |cpu| {
+            cpu.unstable.lxa_magic = magic;
+            cpu.reg_a = 0x01;
+        });
+        assert_eq!(cpu.reg_a, (0x01 | magic) & 0x5a);
+        assert_eq!(cpu.reg_x, cpu.reg_a);
+    }
+}
+
+// Runs a SH* or TAS store to $12F0 + 0x20 = $1310 (a page cross) or to
+// $1200 + 0x10 = $1210 (none) and returns the CPU.
+fn sh_store(opcode: u8, cross: bool, corrupt: bool) -> CPU<FlatMemory> {
+    let base: u16 = if cross { 0x12f0 } else { 0x1200 };
+    let index = if cross { 0x20 } else { 0x10 };
+    run_one(&[opcode, base as u8, (base >> 8) as u8], |cpu| {
+        cpu.unstable = UnstableOpcodes {
+            sh_page_cross_corrupts_address: corrupt,
+            ..UnstableOpcodes::default()
+        };
+        cpu.reg_a = 0xff;
+        cpu.reg_x = 0x0f;
+        cpu.reg_y = 0x0f;
+        // whichever of X and Y indexes, the other stays $0F
+        if opcode == 0x9c {
+            cpu.reg_x = index;
+        } else {
+            cpu.reg_y = index;
+        }
+    })
+}
+
+#[test]
+fn sh_stores_and_with_the_high_byte_plus_one() {
+    // SHA abs,Y stores A & X & $13, SHX X & $13, SHY Y & $13
+    for (opcode, value) in [(0x9f, 0x03), (0x9e, 0x03), (0x9c, 0x03)] {
+        let cpu = sh_store(opcode, false, true);
+        assert_eq!(cpu.mem_peek(0x1210), value, "opcode {:#04x}", opcode);
+    }
+    // TAS abs,Y sets SP = A & X first
+    let cpu = sh_store(0x9b, false, true);
+    assert_eq!(cpu.stp, 0x0f);
+    assert_eq!(cpu.mem_peek(0x1210), 0x03);
+}
+
+#[test]
+fn sh_page_cross_follows_its_setting() {
+    for opcode in [0x9f, 0x9e, 0x9c, 0x9b] {
+        // the value $03 replaces the high byte of $1310
+        let cpu = sh_store(opcode, true, true);
+        assert_eq!(cpu.mem_peek(0x0310), 0x03, "opcode {:#04x}", opcode);
+        assert_eq!(cpu.mem_peek(0x1310), 0x00, "opcode {:#04x}", opcode);
+
+        let cpu = sh_store(opcode, true, false);
+        assert_eq!(cpu.mem_peek(0x1310), 0x03, "opcode {:#04x}", opcode);
+        assert_eq!(cpu.mem_peek(0x0310), 0x00, "opcode {:#04x}", opcode);
+    }
+}